@@ -1,6 +1,96 @@
 CREATE TABLE summary (
-    insertTime timestamp,
+    id bigserial,
+    insert_time timestamptz,
     org varchar,
     repo varchar,
-    coverage jsonb  
-);
\ No newline at end of file
+    coverage jsonb,
+    format_version varchar,
+    gcc_version varchar,
+    labels text[] NOT NULL DEFAULT '{}',
+    commit varchar,
+    pr_number integer,
+    build_url varchar,
+    branch varchar
+);
+
+CREATE TABLE latest_summary (
+    insert_time timestamptz,
+    org varchar,
+    repo varchar,
+    coverage jsonb,
+    format_version varchar,
+    gcc_version varchar,
+    labels text[] NOT NULL DEFAULT '{}',
+    commit varchar,
+    pr_number integer,
+    build_url varchar,
+    branch varchar,
+    PRIMARY KEY (org, repo)
+);
+
+CREATE TABLE repo_config (
+    org varchar,
+    repo varchar,
+    line_threshold double precision,
+    branch_threshold double precision,
+    function_threshold double precision,
+    PRIMARY KEY (org, repo)
+);
+
+CREATE TABLE coverage_goals (
+    org varchar,
+    repo varchar,
+    line_goal double precision,
+    branch_goal double precision,
+    function_goal double precision,
+    PRIMARY KEY (org, repo)
+);
+
+CREATE TABLE annotations (
+    id bigserial PRIMARY KEY,
+    org varchar NOT NULL,
+    repo varchar NOT NULL,
+    summary_time timestamptz NOT NULL,
+    author varchar NOT NULL,
+    text text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE failed_uploads (
+    id bigserial PRIMARY KEY,
+    failed_at timestamptz NOT NULL DEFAULT now(),
+    org varchar NOT NULL,
+    repo varchar NOT NULL,
+    error text NOT NULL,
+    payload text NOT NULL
+);
+
+CREATE TABLE baselines (
+    org varchar,
+    repo varchar,
+    name varchar,
+    summary_time timestamptz NOT NULL,
+    coverage jsonb NOT NULL,
+    PRIMARY KEY (org, repo, name)
+);
+
+CREATE TABLE org_quota (
+    org varchar PRIMARY KEY,
+    max_summaries bigint,
+    max_uploads_per_hour bigint,
+    prune_oldest boolean NOT NULL DEFAULT false
+);
+
+CREATE TABLE repo_secrets (
+    org varchar,
+    repo varchar,
+    secret varchar NOT NULL,
+    PRIMARY KEY (org, repo)
+);
+
+CREATE TABLE repo_info (
+    org varchar,
+    repo varchar,
+    description text NOT NULL,
+    PRIMARY KEY (org, repo)
+);
@@ -0,0 +1,224 @@
+use crate::db::summary::SummaryUpload;
+use crate::LatestCache;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const DEFAULT_BATCH_INTERVAL_MS: u64 = 200;
+const DEFAULT_BATCH_MAX_ENTRIES: usize = 100;
+
+/// Buffers uploaded summaries so a background task can insert them in batches
+#[derive(Clone)]
+pub struct BatchWriter {
+    sender: mpsc::Sender<(String, String, SummaryUpload)>,
+}
+
+/// The configured writer, or `None` when every upload is inserted straight away
+pub type Batcher = Option<BatchWriter>;
+
+impl BatchWriter {
+    /// Queues a summary to be inserted with the next batch, waiting if the buffer is full
+    pub async fn push(
+        &self,
+        org: String,
+        repo: String,
+        upload: SummaryUpload,
+    ) -> anyhow::Result<()> {
+        self.sender
+            .send((org, repo, upload))
+            .await
+            .map_err(|_| anyhow::anyhow!("the batch writer has stopped"))
+    }
+}
+
+/// Starts the batch writer if `$BATCH_WRITES` is set, flushing every `$BATCH_INTERVAL_MS` or once
+/// `$BATCH_MAX_ENTRIES` summaries are buffered, capped to what fits in one query. The returned task
+/// flushes what's left and finishes once every `BatchWriter` has been dropped, so should be awaited
+/// on shutdown.
+pub fn from_env(db: PgPool, latest_cache: LatestCache) -> (Batcher, Option<JoinHandle<()>>) {
+    if !crate::parse_env_var_or("BATCH_WRITES", false) {
+        return (None, None);
+    }
+
+    let interval = Duration::from_millis(crate::parse_env_var_or(
+        "BATCH_INTERVAL_MS",
+        DEFAULT_BATCH_INTERVAL_MS,
+    ));
+    let max_entries = crate::parse_env_var_or("BATCH_MAX_ENTRIES", DEFAULT_BATCH_MAX_ENTRIES)
+        .clamp(1, crate::db::summary::MAX_BATCH_ROWS);
+
+    let (writer, task) = start(db, latest_cache, interval, max_entries);
+    (Some(writer), Some(task))
+}
+
+/// Starts the background task flushing every `interval` or once `max_entries` summaries are buffered
+fn start(
+    db: PgPool,
+    latest_cache: LatestCache,
+    interval: Duration,
+    max_entries: usize,
+) -> (BatchWriter, JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel(max_entries);
+    let task = tokio::spawn(run(db, latest_cache, receiver, interval, max_entries));
+
+    (BatchWriter { sender }, task)
+}
+
+async fn run(
+    db: PgPool,
+    latest_cache: LatestCache,
+    mut receiver: mpsc::Receiver<(String, String, SummaryUpload)>,
+    interval: Duration,
+    max_entries: usize,
+) {
+    let mut buffer = Vec::with_capacity(max_entries);
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Some(entry) => {
+                    buffer.push(entry);
+                    if buffer.len() >= max_entries {
+                        flush(&db, &latest_cache, &mut buffer).await;
+                    }
+                }
+                None => break,
+            },
+            _ = ticker.tick() => flush(&db, &latest_cache, &mut buffer).await,
+        }
+    }
+
+    flush(&db, &latest_cache, &mut buffer).await;
+}
+
+/// Inserts and clears the buffered summaries. If the batch fails, e.g. because one summary is
+/// rejected by the DB, each is retried on its own so the rest are still stored, and any that fail
+/// again are recorded as failed uploads.
+async fn flush(
+    db: &PgPool,
+    latest_cache: &LatestCache,
+    buffer: &mut Vec<(String, String, SummaryUpload)>,
+) {
+    if buffer.is_empty() {
+        return;
+    }
+
+    match crate::db::summary::insert_batch(db, buffer).await {
+        Ok(()) => tracing::debug!("Flushed {} batched summaries", buffer.len()),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to insert {} batched summaries, retrying individually: {}",
+                buffer.len(),
+                e
+            );
+
+            for (org, repo, upload) in buffer.iter() {
+                if let Err(e) = crate::db::summary::insert_into_table(db, org, repo, upload).await {
+                    tracing::error!(
+                        "Failed to insert batched summary for {}/{}: {}",
+                        org,
+                        repo,
+                        e
+                    );
+
+                    // The upload was already acknowledged, so this is the only trace of it
+                    let error = format!("Failed to insert batched summary: {}", e);
+                    let payload = serde_json::to_string(upload).unwrap_or_default();
+                    crate::record_failed_upload(db, org, repo, &error, &payload).await;
+                }
+            }
+        }
+    }
+
+    let mut cache = latest_cache.lock().await;
+    for (org, repo, _) in buffer.drain(..) {
+        cache.remove(&(org, repo));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(line_covered: usize) -> SummaryUpload {
+        serde_json::from_value(crate::test_util::coverage(line_covered, 10)).unwrap()
+    }
+
+    async fn stored(db: &PgPool) -> Vec<(String, i64)> {
+        sqlx::query_as(
+            "SELECT repo, (coverage->>'line_covered')::bigint FROM summary ORDER BY repo",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn flushes_the_buffer_on_shutdown(db: PgPool) {
+        crate::test_util::setup(&db).await;
+        let (writer, task) = start(
+            db.clone(),
+            LatestCache::default(),
+            Duration::from_secs(3600),
+            10,
+        );
+        // Let the interval's immediate first tick pass, so the next is an hour away
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        writer
+            .push("org".into(), "a".into(), upload(1))
+            .await
+            .unwrap();
+        writer
+            .push("org".into(), "b".into(), upload(2))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(stored(&db).await.is_empty());
+
+        drop(writer);
+        task.await.unwrap();
+
+        assert_eq!(stored(&db).await, [("a".into(), 1), ("b".into(), 2)]);
+    }
+
+    #[sqlx::test]
+    async fn flushes_once_the_buffer_is_full(db: PgPool) {
+        crate::test_util::setup(&db).await;
+        let (writer, task) = start(
+            db.clone(),
+            LatestCache::default(),
+            Duration::from_secs(3600),
+            2,
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        writer
+            .push("org".into(), "a".into(), upload(1))
+            .await
+            .unwrap();
+        writer
+            .push("org".into(), "b".into(), upload(2))
+            .await
+            .unwrap();
+        writer
+            .push("org".into(), "c".into(), upload(3))
+            .await
+            .unwrap();
+
+        // The first two go out together, without waiting for the interval
+        for _ in 0..100 {
+            if !stored(&db).await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(stored(&db).await, [("a".into(), 1), ("b".into(), 2)]);
+
+        drop(writer);
+        task.await.unwrap();
+        assert_eq!(stored(&db).await.len(), 3);
+    }
+}
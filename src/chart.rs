@@ -0,0 +1,113 @@
+use plotters::prelude::*;
+
+/// A line to draw on a chart, through points in pixels from the top left
+pub struct Line {
+    pub points: Vec<(f64, f64)>,
+    /// Thickness in pixels, or the diameter of the dot drawn for a lone point
+    pub width: u32,
+    pub colour: [u8; 3],
+}
+
+/// Draws `lines` in order onto a white `width` by `height` image and encodes it as an 8-bit RGB
+/// PNG. A line with a single point has nothing to join, so is drawn as a dot instead.
+pub fn render_png(width: u32, height: u32, lines: &[Line]) -> anyhow::Result<Vec<u8>> {
+    let mut pixels = vec![0; width as usize * height as usize * 3];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        for line in lines {
+            let [r, g, b] = line.colour;
+            let colour = RGBColor(r, g, b);
+            let points: Vec<(i32, i32)> = line
+                .points
+                .iter()
+                .map(|&(x, y)| (x.round() as i32, y.round() as i32))
+                .collect();
+
+            match points.as_slice() {
+                [point] => root.draw(&Circle::new(*point, line.width / 2, colour.filled()))?,
+                _ => root.draw(&PathElement::new(points, colour.stroke_width(line.width)))?,
+            }
+        }
+
+        root.present()?;
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(png)
+}
+
+/// Formats a colour for CSS and SVG, e.g. `#1f77b4`
+pub fn css_colour([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLUE: [u8; 3] = [31, 119, 180];
+    const ORANGE: [u8; 3] = [255, 127, 14];
+
+    /// Decodes a PNG, returning its header's details and its pixels
+    fn decode(png: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        pixels.truncate(info.buffer_size());
+
+        (info, pixels)
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 3] {
+        let offset = (y * width + x) as usize * 3;
+        pixels[offset..offset + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn renders_lines_as_a_png() {
+        let lines = [
+            Line {
+                points: vec![(0.0, 10.0), (40.0, 10.0)],
+                width: 2,
+                colour: BLUE,
+            },
+            Line {
+                points: vec![(30.0, 5.0)],
+                width: 5,
+                colour: ORANGE,
+            },
+        ];
+        let png = render_png(40, 20, &lines).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // The header is the first chunk, 13 bytes long
+        assert_eq!(&png[8..16], b"\0\0\0\x0dIHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 40, 0, 0, 0, 20]);
+
+        let (info, pixels) = decode(&png);
+        assert_eq!((info.width, info.height), (40, 20));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+
+        assert_eq!(pixel(&pixels, 40, 0, 0), [255, 255, 255]);
+        assert_eq!(pixel(&pixels, 40, 10, 10), BLUE);
+        assert_eq!(pixel(&pixels, 40, 10, 15), [255, 255, 255]);
+        assert_eq!(pixel(&pixels, 40, 30, 5), ORANGE);
+    }
+
+    #[test]
+    fn formats_css_colours() {
+        assert_eq!(css_colour(BLUE), "#1f77b4");
+        assert_eq!(css_colour([0, 0, 0]), "#000000");
+    }
+}
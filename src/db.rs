@@ -1,9 +1,40 @@
 use lazy_static::lazy_static;
+use serde::Serialize;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgConnection;
 use sqlx::PgPool;
 use sqlx::Pool;
 use sqlx::Postgres;
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+const DEFAULT_CONNECT_RETRIES: u32 = 10;
+const DEFAULT_STATEMENT_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_SCHEMA: &str = "public";
+const DEFAULT_SLOW_QUERY_MS: u64 = 500;
+/// Same as sqlx's own defaults
+const DEFAULT_MAX_LIFETIME_SECS: u64 = 30 * 60;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+/// How much longer than the request timeout a statement may run, so the request's own timeout is
+/// what the client sees rather than the cancelled query's error
+const REQUEST_DEADLINE_GRACE_MS: u64 = 100;
+/// Tables besides `summary` holding rows for a single repo, which follow it when it's moved
+const REPO_TABLES: [&str; 7] = [
+    "repo_config",
+    "coverage_goals",
+    "annotations",
+    "failed_uploads",
+    "baselines",
+    "repo_secrets",
+    "repo_info",
+];
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
 lazy_static! {
     static ref CONNECTION_URL: String = {
         let pg_password = fetch_env_var_exiting("POSTGRES_PASSWORD");
@@ -11,6 +42,10 @@ lazy_static! {
 
         format!("postgres://postgres:{pg_password}@db/{pg_db}")
     };
+    static ref SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(crate::parse_env_var_or(
+        "SLOW_QUERY_MS",
+        DEFAULT_SLOW_QUERY_MS,
+    ));
 }
 
 #[derive(Error, Debug)]
@@ -19,6 +54,9 @@ pub enum DbError {
     Sqlx(#[from] sqlx::Error),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+    /// Storing the summary would take its org over its quota, so nothing was stored
+    #[error("org quota exceeded")]
+    QuotaExceeded(quota::QuotaExceeded),
 }
 
 /// Fetches the environment variable `key` from the process, exiting the process on error.
@@ -37,17 +75,302 @@ fn fetch_env_var_exiting(key: &str) -> String {
 
 /// Connects to the DB instance and performs any required setup (Like creating tables etc).
 pub async fn connect_and_setup() -> Result<Pool<Postgres>, sqlx::Error> {
-    let db_pool = PgPool::connect(&CONNECTION_URL).await?;
+    let max_connections = crate::parse_env_var_or("DB_MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS);
+
+    let retries = crate::parse_env_var_or("DB_CONNECT_RETRIES", DEFAULT_CONNECT_RETRIES);
+    let statement_timeout_ms = capped_statement_timeout(
+        crate::parse_env_var_or("DB_STATEMENT_TIMEOUT_MS", DEFAULT_STATEMENT_TIMEOUT_MS),
+        *crate::REQUEST_TIMEOUT,
+    );
+
+    // Recycle connections so ones silently dropped by a load balancer don't linger in the pool.
+    // Setting either to 0 keeps connections for as long as they're usable.
+    let max_lifetime = secs_or_never(crate::parse_env_var_or(
+        "DB_MAX_LIFETIME_SECS",
+        DEFAULT_MAX_LIFETIME_SECS,
+    ));
+    let idle_timeout = secs_or_never(crate::parse_env_var_or(
+        "DB_IDLE_TIMEOUT_SECS",
+        DEFAULT_IDLE_TIMEOUT_SECS,
+    ));
+    let describe = |timeout: Option<Duration>| match timeout {
+        Some(timeout) => format!("{}s", timeout.as_secs()),
+        None => "never".to_string(),
+    };
+    tracing::info!(
+        "DB connection max lifetime: {}, idle timeout: {}",
+        describe(max_lifetime),
+        describe(idle_timeout)
+    );
+
+    // Every query relies on the search path to find the tables, so they all live in `DB_SCHEMA`
+    let schema = std::env::var("DB_SCHEMA").unwrap_or(DEFAULT_SCHEMA.to_string());
+
+    // Have Postgres abort stuck queries rather than let them hold a connection indefinitely.
+    // Queries that are expected to take longer, like exports and batch inserts, opt out with
+    // `disable_statement_timeout`.
+    let connect_options = PgConnectOptions::from_str(&CONNECTION_URL)?.options([
+        ("statement_timeout", statement_timeout_ms.to_string()),
+        ("search_path", escape_option(&quote_identifier(&schema))),
+    ]);
+
+    // The DB may still be starting up alongside us, so keep trying for a while before giving up
+    let db_pool = retry_with_backoff(retries, || {
+        pool_options(max_connections, max_lifetime, idle_timeout)
+            .connect_with(connect_options.clone())
+    })
+    .await?;
+
+    sqlx::query(&format!(
+        "CREATE SCHEMA IF NOT EXISTS {}",
+        quote_identifier(&schema)
+    ))
+    .execute(&db_pool)
+    .await?;
 
-    let _ = summary::setup_table(&db_pool).await?;
+    setup_tables(&db_pool).await?;
+
+    if crate::parse_env_var_or("DB_WARMUP", false) {
+        warm_up(&db_pool, max_connections).await?;
+    }
 
     Ok(db_pool)
 }
 
+/// Creates every table that doesn't exist yet, in whichever schema is first on the search path
+pub async fn setup_tables(db: &PgPool) -> Result<(), sqlx::Error> {
+    let _ = summary::setup_table(db).await?;
+    let _ = repo_config::setup_table(db).await?;
+    let _ = goals::setup_table(db).await?;
+    let _ = annotations::setup_table(db).await?;
+    let _ = failures::setup_table(db).await?;
+    let _ = baselines::setup_table(db).await?;
+    let _ = quota::setup_table(db).await?;
+    let _ = repo_secrets::setup_table(db).await?;
+    let _ = repo_info::setup_table(db).await?;
+
+    Ok(())
+}
+
+/// Caps a statement timeout in milliseconds to just past `request_timeout`, as a query outliving
+/// its request's deadline has been abandoned and Postgres should cancel it rather than let it keep
+/// running on the connection. A statement timeout of 0 means none.
+pub(crate) fn capped_statement_timeout(
+    statement_timeout_ms: u64,
+    request_timeout: Option<Duration>,
+) -> u64 {
+    let Some(request_timeout) = request_timeout else {
+        return statement_timeout_ms;
+    };
+
+    let deadline_ms = request_timeout.as_millis() as u64 + REQUEST_DEADLINE_GRACE_MS;
+    if statement_timeout_ms == 0 || statement_timeout_ms > deadline_ms {
+        deadline_ms
+    } else {
+        statement_timeout_ms
+    }
+}
+
+/// Options for a pool of up to `max_connections`, closing connections once they're older than
+/// `max_lifetime` or have been idle for `idle_timeout`
+fn pool_options(
+    max_connections: u32,
+    max_lifetime: Option<Duration>,
+    idle_timeout: Option<Duration>,
+) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(max_connections)
+        .max_lifetime(max_lifetime)
+        .idle_timeout(idle_timeout)
+}
+
+/// Converts a number of seconds from the environment to a timeout, where 0 means no timeout
+fn secs_or_never(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Quotes `name` for use as an SQL identifier, e.g. a schema name
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Escapes `value` for the startup options Postgres is sent on connecting, which it splits at
+/// unescaped whitespace
+fn escape_option(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || c.is_ascii_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Runs `op` until it succeeds, retrying up to `retries` times with an exponentially increasing delay
+/// between attempts. Returns the last error once the retries are exhausted.
+async fn retry_with_backoff<T, E, F, Fut>(retries: u32, mut op: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 0;
+
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "DB connection failed ({}), retrying in {:?} (attempt {}/{})",
+                    e,
+                    delay,
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Awaits `query`, logging a warning naming it if it takes longer than `$SLOW_QUERY_MS`, to surface
+/// missing indexes and slow aggregations
+pub(crate) async fn log_if_slow<T>(name: &str, query: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let resp = query.await;
+
+    let elapsed = start.elapsed();
+    if elapsed > *SLOW_QUERY_THRESHOLD {
+        tracing::warn!("Slow query {} took {:?}", name, elapsed);
+    }
+
+    resp
+}
+
+/// Checks the DB is reachable
+pub async fn ping(db: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1").execute(db).await?;
+
+    Ok(())
+}
+
+/// Lifts the statement timeout until the end of the transaction `conn` is in, for the few queries
+/// allowed to run for longer than a request may, like exports. The connection's own timeout is
+/// back in force once the transaction ends.
+pub async fn disable_statement_timeout(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SET LOCAL statement_timeout = 0")
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Establishes `count` connections up front, so the first requests after startup don't have to
+async fn warm_up(db: &PgPool, count: u32) -> Result<(), sqlx::Error> {
+    let start = Instant::now();
+
+    // Hold every connection at once so the pool is forced to open new ones, rather than reusing
+    let connections = futures_util::future::try_join_all((0..count).map(|_| db.acquire())).await?;
+    drop(connections);
+
+    tracing::info!(
+        "Warmed up {} DB connections in {:?}",
+        count,
+        start.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Overview of everything stored
+#[derive(Serialize, sqlx::FromRow, Clone, Debug)]
+pub struct Stats {
+    /// Total number of summaries stored
+    pub summaries: i64,
+    /// Number of distinct orgs
+    pub orgs: i64,
+    /// Number of distinct org/repo pairs
+    pub repos: i64,
+}
+
+/// Counts the stored summaries, orgs and repos
+pub async fn fetch_stats(db: &PgPool) -> Result<Stats, DbError> {
+    let stats: Stats = log_if_slow(
+        "fetch_stats",
+        sqlx::query_as(
+            r#"SELECT count(*) AS summaries,
+                count(DISTINCT org) AS orgs,
+                count(DISTINCT (org, repo)) AS repos
+            FROM summary"#,
+        )
+        .fetch_one(db),
+    )
+    .await?;
+
+    Ok(stats)
+}
+
+/// Moves every row belonging to `from_org`/`from_repo` over to `to_org`/`to_repo` within a single
+/// transaction, returning the number of rows moved.
+pub async fn move_repo(
+    db: &PgPool,
+    from_org: &str,
+    from_repo: &str,
+    to_org: &str,
+    to_repo: &str,
+) -> Result<u64, DbError> {
+    let mut tx = db.begin().await?;
+
+    // The summaries also keep `latest_summary` in sync, so they're moved by their own module
+    let mut moved = summary::move_repo(&mut tx, from_org, from_repo, to_org, to_repo).await?;
+    for table in REPO_TABLES {
+        moved += move_rows(&mut tx, table, from_org, from_repo, to_org, to_repo).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(moved)
+}
+
+/// Re-keys every row of `table` belonging to `from_org`/`from_repo` to `to_org`/`to_repo`
+async fn move_rows(
+    conn: &mut PgConnection,
+    table: &str,
+    from_org: &str,
+    from_repo: &str,
+    to_org: &str,
+    to_repo: &str,
+) -> Result<u64, sqlx::Error> {
+    let resp = sqlx::query(&format!(
+        "UPDATE {table} SET org = $3, repo = $4 WHERE org = $1 AND repo = $2"
+    ))
+    .bind(from_org)
+    .bind(from_repo)
+    .bind(to_org)
+    .bind(to_repo)
+    .execute(conn)
+    .await?;
+
+    Ok(resp.rows_affected())
+}
+
 pub mod summary {
-    use crate::db::DbError;
+    use crate::db::{disable_statement_timeout, log_if_slow, quota, DbError};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use futures_core::stream::BoxStream;
+    use lazy_static::lazy_static;
     use serde::{ser::SerializeStruct, Deserialize, Serialize};
-    use sqlx::{postgres::PgQueryResult, PgPool};
+    use sqlx::{
+        postgres::PgQueryResult,
+        types::chrono::{DateTime, Utc},
+        PgConnection, PgPool, Postgres, QueryBuilder,
+    };
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
     // GCOV generates the JSON with flat fields in the form "branch_covered", "function_covered", etc
     // This means we can extract the commonality within `Coverage`
@@ -55,8 +378,63 @@ pub mod summary {
     serde_with::with_prefix!(prefix_function "function_");
     serde_with::with_prefix!(prefix_line "line_");
 
+    const DEFAULT_PERCENT_DECIMALS: i32 = 2;
+
+    /// Columns selected to build a `SummaryTableEntry`
+    const ENTRY_COLUMNS: &str =
+        "insert_time, org, repo, coverage, format_version, gcc_version, labels, commit, pr_number, build_url, branch";
+
+    lazy_static! {
+        /// Number of decimal places percentages are rounded to when returned to clients
+        static ref PERCENT_DECIMALS: i32 =
+            crate::parse_env_var_or("PERCENT_DECIMALS", DEFAULT_PERCENT_DECIMALS);
+        /// Query streamed by `stream_all`, which needs to outlive the function building it
+        static ref STREAM_ALL_QUERY: String =
+            format!("SELECT {ENTRY_COLUMNS} FROM summary ORDER BY insert_time, id");
+    }
+
+    /// Rounds a percentage to `PERCENT_DECIMALS` decimal places for output
+    pub fn round_percent(percent: f64) -> f64 {
+        let scale = 10f64.powi(*PERCENT_DECIMALS);
+        (percent * scale).round() / scale
+    }
+
+    /// Serializes a percentage rounded with `round_percent`, for use with `#[serde(serialize_with)]`.
+    /// Stored values keep their full precision.
+    pub fn serialize_percent<S>(percent: &f64, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_f64(round_percent(*percent))
+    }
+
+    /// Copies a stored coverage blob with every `*_percent` field, and the `percent` of each extra
+    /// metric, rounded with `round_percent`
+    fn round_coverage_percents(coverage: &serde_json::Value) -> serde_json::Value {
+        let mut coverage = coverage.clone();
+
+        if let Some(fields) = coverage.as_object_mut() {
+            for (key, value) in fields.iter_mut() {
+                if let Some(percent) = value.as_f64().filter(|_| key.ends_with("_percent")) {
+                    *value = round_percent(percent).into();
+                }
+            }
+
+            let extra = fields.get_mut("extra").and_then(|e| e.as_object_mut());
+            for metric in extra.into_iter().flat_map(|e| e.values_mut()) {
+                if let Some(value) = metric.get_mut("percent") {
+                    if let Some(percent) = value.as_f64() {
+                        *value = round_percent(percent).into();
+                    }
+                }
+            }
+        }
+
+        coverage
+    }
+
     /// Represents a test coverage
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct Coverage {
         /// Number of cases covered
         pub covered: usize,
@@ -66,8 +444,38 @@ pub mod summary {
         pub percent: f64,
     }
 
+    impl Coverage {
+        /// Creates a coverage from its counts, computing the percentage (0 when there are no cases)
+        pub fn from_counts(covered: usize, total: usize) -> Self {
+            let percent = if total == 0 {
+                0.0
+            } else {
+                covered as f64 / total as f64 * 100.0
+            };
+
+            Coverage {
+                covered,
+                total,
+                percent,
+            }
+        }
+
+        /// Checks the counts are self-consistent, returning the reason if they aren't. The percentage
+        /// isn't checked, since it's recomputed from the counts before anything is stored.
+        pub fn validate(&self) -> Result<(), String> {
+            if self.covered > self.total {
+                return Err(format!(
+                    "covered ({}) exceeds total ({})",
+                    self.covered, self.total
+                ));
+            }
+
+            Ok(())
+        }
+    }
+
     /// Represents a GCOV JSON coverage summary report
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Clone)]
     pub struct CoverageSummary {
         #[serde(flatten, with = "prefix_branch")]
         pub branch: Coverage,
@@ -75,19 +483,279 @@ pub mod summary {
         pub function: Coverage,
         #[serde(flatten, with = "prefix_line")]
         pub line: Coverage,
+        /// Any further metrics the coverage tool reports, e.g. `condition` or `mcdc`, by name. They
+        /// are stored and returned but don't count towards thresholds or the overall percentage.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub extra: BTreeMap<String, Coverage>,
+    }
+
+    impl CoverageSummary {
+        /// Combines several summaries into one by summing the counts of each metric, e.g. merging the
+        /// output of several test binaries. Merging nothing gives an empty summary, and `None` is
+        /// returned if any metric's counts overflow.
+        pub fn merge(summaries: &[CoverageSummary]) -> Option<CoverageSummary> {
+            let add = |(covered, total): (usize, usize), c: &Coverage| {
+                Some((covered.checked_add(c.covered)?, total.checked_add(c.total)?))
+            };
+            let sum = |metric: fn(&CoverageSummary) -> &Coverage| {
+                let (covered, total) = summaries.iter().map(metric).try_fold((0, 0), add)?;
+
+                Some(Coverage::from_counts(covered, total))
+            };
+
+            // A summary without one of the extra metrics counts as having no cases of it
+            let mut extra_counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+            for (name, c) in summaries.iter().flat_map(|s| &s.extra) {
+                let counts = extra_counts.entry(name).or_default();
+                *counts = add(*counts, c)?;
+            }
+
+            Some(CoverageSummary {
+                branch: sum(|s| &s.branch)?,
+                function: sum(|s| &s.function)?,
+                line: sum(|s| &s.line)?,
+                extra: extra_counts
+                    .into_iter()
+                    .map(|(name, (covered, total))| {
+                        (name.to_string(), Coverage::from_counts(covered, total))
+                    })
+                    .collect(),
+            })
+        }
+
+        /// The same summary with each percentage recomputed from its counts, in case the client's
+        /// disagrees with them
+        pub fn recomputed(&self) -> CoverageSummary {
+            let recompute = |c: &Coverage| Coverage::from_counts(c.covered, c.total);
+
+            CoverageSummary {
+                branch: recompute(&self.branch),
+                function: recompute(&self.function),
+                line: recompute(&self.line),
+                extra: self
+                    .extra
+                    .iter()
+                    .map(|(name, c)| (name.clone(), recompute(c)))
+                    .collect(),
+            }
+        }
+
+        /// Whether there are no cases at all, which usually means no tests were run
+        pub fn is_empty(&self) -> bool {
+            self.branch.total == 0 && self.function.total == 0 && self.line.total == 0
+        }
+
+        /// Percentage of all cases covered across every metric
+        pub fn overall_percent(&self) -> f64 {
+            let metrics = [&self.branch, &self.function, &self.line];
+            let covered: usize = metrics.iter().map(|c| c.covered).sum();
+            let total: usize = metrics.iter().map(|c| c.total).sum();
+
+            if total == 0 {
+                0.0
+            } else {
+                covered as f64 / total as f64 * 100.0
+            }
+        }
+
+        /// Validates each of the coverage metrics, returning every failure prefixed by the metric name
+        pub fn validate(&self) -> Result<(), Vec<String>> {
+            let canonical = [
+                ("branch", &self.branch),
+                ("function", &self.function),
+                ("line", &self.line),
+            ];
+            let extra = self.extra.iter().map(|(name, c)| (name.as_str(), c));
+
+            let errors: Vec<String> = canonical
+                .into_iter()
+                .chain(extra)
+                .filter_map(|(name, coverage)| {
+                    coverage.validate().err().map(|e| format!("{name}: {e}"))
+                })
+                .collect();
+
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors)
+            }
+        }
+    }
+
+    /// Represents the toolchain information GCOV includes alongside its coverage output.
+    /// Older versions of GCOV don't emit these, so both are optional.
+    #[derive(Serialize, Deserialize, sqlx::FromRow, Default, Debug)]
+    pub struct ToolInfo {
+        /// Version of the GCOV JSON format
+        pub format_version: Option<String>,
+        /// Version of GCC that produced the coverage
+        pub gcc_version: Option<String>,
+    }
+
+    /// Represents the body of a summary upload, i.e. the GCOV JSON coverage summary report
+    #[derive(Serialize, Deserialize)]
+    pub struct SummaryUpload {
+        #[serde(flatten)]
+        pub coverage: CoverageSummary,
+        #[serde(flatten)]
+        pub tool: ToolInfo,
+        /// Arbitrary labels to tag the run with, e.g. "nightly"
+        #[serde(default)]
+        pub labels: Vec<String>,
+        /// Commit the coverage was produced from. Uploading again for the same commit replaces the
+        /// previous summary rather than adding another.
+        pub commit: Option<String>,
+        /// Pull request the coverage was produced for, if any
+        pub pr_number: Option<i32>,
+        /// CI build that produced the coverage, if any
+        pub build_url: Option<String>,
+        /// Branch the coverage was produced on, if any
+        pub branch: Option<String>,
+        /// Overrides the insertion time, for backfilling historical coverage. Only honoured for
+        /// admin requests.
+        pub insert_time: Option<DateTime<Utc>>,
     }
 
     /// Represents a row in the 'summary' db table
     #[derive(sqlx::FromRow, Debug)]
     pub struct SummaryTableEntry {
         /// Row insertion time
-        pub insert_time: sqlx::types::chrono::DateTime<sqlx::types::chrono::Utc>,
+        pub insert_time: DateTime<Utc>,
         /// Gitea organisation the repo belongs to
         pub org: String,
         /// Gitea repository the summary belongs to
         pub repo: String,
         /// Test coverage summary
         pub coverage: sqlx::types::JsonValue,
+        /// Toolchain that produced the coverage
+        #[sqlx(flatten)]
+        pub tool: ToolInfo,
+        /// Labels the run was tagged with
+        pub labels: Vec<String>,
+        /// Commit the coverage was produced from
+        pub commit: Option<String>,
+        /// Pull request the coverage was produced for
+        pub pr_number: Option<i32>,
+        /// CI build that produced the coverage
+        pub build_url: Option<String>,
+        /// Branch the coverage was produced on
+        pub branch: Option<String>,
+    }
+
+    impl SummaryTableEntry {
+        /// Parses the stored coverage back into a `CoverageSummary`
+        pub fn coverage_summary(&self) -> Result<CoverageSummary, serde_json::Error> {
+            CoverageSummary::deserialize(&self.coverage)
+        }
+
+        /// The entry exactly as stored, i.e. with the exact insert time and unrounded coverage, unlike
+        /// its `Serialize` impl
+        pub fn raw(&self) -> serde_json::Value {
+            serde_json::json!({
+                "insert_time": self.insert_time,
+                "org": self.org,
+                "repo": self.repo,
+                "coverage": self.coverage,
+                "format_version": self.tool.format_version,
+                "gcc_version": self.tool.gcc_version,
+                "labels": self.labels,
+                "commit": self.commit,
+                "pr_number": self.pr_number,
+                "build_url": self.build_url,
+                "branch": self.branch,
+            })
+        }
+    }
+
+    /// Represents a summary with its coverage flattened down to the percentage of each metric
+    #[derive(Serialize, Clone, Debug)]
+    pub struct FlatSummary {
+        pub org: String,
+        pub repo: String,
+        /// Unix timestamp the summary was stored at
+        pub insert_time: i64,
+        #[serde(serialize_with = "serialize_percent")]
+        pub line_percent: f64,
+        #[serde(serialize_with = "serialize_percent")]
+        pub branch_percent: f64,
+        #[serde(serialize_with = "serialize_percent")]
+        pub function_percent: f64,
+        /// Percentage of each extra metric, by name, already rounded with `round_percent`
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        pub extra_percents: BTreeMap<String, f64>,
+    }
+
+    impl TryFrom<&SummaryTableEntry> for FlatSummary {
+        type Error = serde_json::Error;
+
+        fn try_from(entry: &SummaryTableEntry) -> Result<Self, Self::Error> {
+            let coverage = entry.coverage_summary()?;
+
+            Ok(FlatSummary {
+                org: entry.org.clone(),
+                repo: entry.repo.clone(),
+                insert_time: entry.insert_time.timestamp(),
+                line_percent: coverage.line.percent,
+                branch_percent: coverage.branch.percent,
+                function_percent: coverage.function.percent,
+                extra_percents: coverage
+                    .extra
+                    .iter()
+                    .map(|(name, c)| (name.clone(), round_percent(c.percent)))
+                    .collect(),
+            })
+        }
+    }
+
+    /// A summary alongside its row id, which orders summaries stored at the same time
+    #[derive(sqlx::FromRow, Debug)]
+    pub struct NumberedEntry {
+        pub id: i64,
+        #[sqlx(flatten)]
+        pub entry: SummaryTableEntry,
+    }
+
+    /// Position within the newest-first list of every summary, i.e. the last row of a page. Clients
+    /// get it as an opaque token, so its format can change.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Cursor {
+        pub insert_time: DateTime<Utc>,
+        pub id: i64,
+    }
+
+    impl Cursor {
+        /// Creates a cursor pointing just past `row`
+        pub fn after(row: &NumberedEntry) -> Self {
+            Cursor {
+                insert_time: row.entry.insert_time,
+                id: row.id,
+            }
+        }
+
+        pub fn encode(&self) -> String {
+            let keyset = format!("{}:{}", self.insert_time.timestamp_micros(), self.id);
+            URL_SAFE_NO_PAD.encode(keyset)
+        }
+
+        /// Decodes a token from `encode`, returning `None` if it's malformed
+        pub fn decode(token: &str) -> Option<Self> {
+            let keyset = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+            let (micros, id) = keyset.split_once(':')?;
+
+            Some(Cursor {
+                insert_time: DateTime::from_timestamp_micros(micros.parse().ok()?)?,
+                id: id.parse().ok()?,
+            })
+        }
+    }
+
+    /// A commit that a summary was uploaded for
+    #[derive(Serialize, sqlx::FromRow, Debug)]
+    pub struct CommitEntry {
+        pub commit: String,
+        /// Unix timestamp the commit's summary was stored at
+        pub insert_time: i64,
     }
 
     impl Serialize for SummaryTableEntry {
@@ -95,18 +763,25 @@ pub mod summary {
         where
             S: serde::Serializer,
         {
-            let mut state = serializer.serialize_struct("SummaryTableEntry", 4)?;
+            let mut state = serializer.serialize_struct("SummaryTableEntry", 11)?;
 
             state.serialize_field("insert_time", &self.insert_time.timestamp())?;
             state.serialize_field("org", &self.org)?;
             state.serialize_field("repo", &self.repo)?;
-            state.serialize_field("coverage", &self.coverage)?;
+            state.serialize_field("coverage", &round_coverage_percents(&self.coverage))?;
+            state.serialize_field("format_version", &self.tool.format_version)?;
+            state.serialize_field("gcc_version", &self.tool.gcc_version)?;
+            state.serialize_field("labels", &self.labels)?;
+            state.serialize_field("commit", &self.commit)?;
+            state.serialize_field("pr_number", &self.pr_number)?;
+            state.serialize_field("build_url", &self.build_url)?;
+            state.serialize_field("branch", &self.branch)?;
 
             state.end()
         }
     }
 
-    /// Creates the summary db table if it doesn't exist
+    /// Creates the summary db table if it doesn't exist, adding any columns missing from older tables
     pub(super) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
         sqlx::query(
             r#"CREATE TABLE IF NOT EXISTS summary (
@@ -117,36 +792,2077 @@ pub mod summary {
                     );"#,
         )
         .execute(db)
+        .await?;
+
+        sqlx::query(
+            r#"ALTER TABLE summary
+                        ADD COLUMN IF NOT EXISTS format_version varchar,
+                        ADD COLUMN IF NOT EXISTS gcc_version varchar,
+                        ADD COLUMN IF NOT EXISTS labels text[] NOT NULL DEFAULT '{}',
+                        ADD COLUMN IF NOT EXISTS commit varchar,
+                        ADD COLUMN IF NOT EXISTS pr_number integer,
+                        ADD COLUMN IF NOT EXISTS build_url varchar,
+                        ADD COLUMN IF NOT EXISTS branch varchar,
+                        ADD COLUMN IF NOT EXISTS id bigserial;"#,
+        )
+        .execute(db)
+        .await?;
+
+        // Backs the keyset pagination of `fetch_page`
+        sqlx::query(
+            r#"CREATE INDEX IF NOT EXISTS summary_page_idx
+                        ON summary (insert_time DESC, id DESC);"#,
+        )
+        .execute(db)
+        .await?;
+
+        // Allows at most one summary per commit, so re-uploads for a commit can replace it
+        sqlx::query(
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS summary_commit_idx
+                        ON summary (org, repo, commit) WHERE commit IS NOT NULL;"#,
+        )
+        .execute(db)
+        .await?;
+
+        // The latest summary of each repo, kept in sync by `refresh_latest` so reads don't have to
+        // search the whole history
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS latest_summary (
+                        insert_time timestamptz,
+                        org varchar,
+                        repo varchar,
+                        coverage jsonb,
+                        format_version varchar,
+                        gcc_version varchar,
+                        labels text[] NOT NULL DEFAULT '{}',
+                        commit varchar,
+                        pr_number integer,
+                        build_url varchar,
+                        branch varchar,
+                        PRIMARY KEY (org, repo)
+                    );"#,
+        )
+        .execute(db)
+        .await?;
+
+        sqlx::query(
+            r#"ALTER TABLE latest_summary
+                        ADD COLUMN IF NOT EXISTS pr_number integer,
+                        ADD COLUMN IF NOT EXISTS build_url varchar,
+                        ADD COLUMN IF NOT EXISTS branch varchar;"#,
+        )
+        .execute(db)
+        .await?;
+
+        // Fill in any repos missing from it, i.e. everything when upgrading from before it existed
+        sqlx::query(&format!(
+            r#"INSERT INTO latest_summary ({ENTRY_COLUMNS})
+                SELECT DISTINCT ON (org, repo) {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org IS NOT NULL AND repo IS NOT NULL
+                ORDER BY org, repo, insert_time DESC
+                ON CONFLICT (org, repo) DO NOTHING"#
+        ))
+        .execute(db)
         .await
     }
 
-    /// Inserts a test coverage summary into the summary db table
+    /// Replaces the `latest_summary` row of `org`/`repo` with its latest summary in the history
+    async fn refresh_latest(
+        conn: &mut PgConnection,
+        org: &str,
+        repo: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM latest_summary WHERE org = $1 AND repo = $2")
+            .bind(org)
+            .bind(repo)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(&format!(
+            r#"INSERT INTO latest_summary ({ENTRY_COLUMNS})
+                SELECT {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org = $1 AND repo = $2
+                ORDER BY insert_time DESC
+                LIMIT 1"#
+        ))
+        .bind(org)
+        .bind(repo)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Moves the `latest_summary` row of `org`/`repo` forward to its latest summary in the history,
+    /// for after summaries were only added. The upsert is guarded on `insert_time` so a concurrent,
+    /// older upload that commits later never overwrites a newer latest.
+    async fn advance_latest(
+        conn: &mut PgConnection,
+        org: &str,
+        repo: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            r#"INSERT INTO latest_summary AS latest ({ENTRY_COLUMNS})
+                SELECT {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org = $1 AND repo = $2
+                ORDER BY insert_time DESC
+                LIMIT 1
+                ON CONFLICT (org, repo) DO UPDATE SET
+                    insert_time = excluded.insert_time,
+                    coverage = excluded.coverage,
+                    format_version = excluded.format_version,
+                    gcc_version = excluded.gcc_version,
+                    labels = excluded.labels,
+                    commit = excluded.commit,
+                    pr_number = excluded.pr_number,
+                    build_url = excluded.build_url,
+                    branch = excluded.branch
+                WHERE excluded.insert_time > latest.insert_time"#
+        ))
+        .bind(org)
+        .bind(repo)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The outcome of `insert_into_table`
+    pub struct Inserted {
+        pub insert_time: DateTime<Utc>,
+        /// The repo's latest summary from before the insert, if it had one
+        pub previous: Option<SummaryTableEntry>,
+        /// Whether a summary already stored for the upload's commit was replaced
+        pub replaced: bool,
+    }
+
+    /// Inserts a test coverage summary into the summary db table. If a summary was already stored
+    /// for the upload's commit it's replaced instead.
     pub async fn insert_into_table(
         db: &PgPool,
         org: &str,
         repo: &str,
-        coverage: &CoverageSummary,
+        upload: &SummaryUpload,
+    ) -> Result<Inserted, DbError> {
+        // Only the counts are trusted, so stored percentages always agree with them
+        let json_coverage = serde_json::to_value(upload.coverage.recomputed())?;
+
+        let mut tx = db.begin().await?;
+
+        // Locked before anything else, so concurrent uploads to the org apply its quota in turn
+        let quota = quota::lock(&mut tx, org).await?;
+
+        // Locked so concurrent uploads to the repo each compare against the one before
+        let previous: Option<SummaryTableEntry> = sqlx::query_as(&format!(
+            "SELECT {ENTRY_COLUMNS} FROM latest_summary WHERE org = $1 AND repo = $2 FOR UPDATE"
+        ))
+        .bind(org)
+        .bind(repo)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // `xmax` is only set on a row that was updated, i.e. by the ON CONFLICT branch
+        let (insert_time, replaced) = sqlx::query_as(
+            r#"INSERT INTO summary (insert_time, org, repo, coverage, format_version, gcc_version, labels, commit, pr_number, build_url, branch)
+                VALUES (COALESCE($8, now()), $1, $2, $3, $4, $5, $6, $7, $9, $10, $11)
+                ON CONFLICT (org, repo, commit) WHERE commit IS NOT NULL DO UPDATE SET
+                    insert_time = excluded.insert_time,
+                    coverage = excluded.coverage,
+                    format_version = excluded.format_version,
+                    gcc_version = excluded.gcc_version,
+                    labels = excluded.labels,
+                    pr_number = excluded.pr_number,
+                    build_url = excluded.build_url,
+                    branch = excluded.branch
+                RETURNING insert_time, xmax <> 0"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(json_coverage)
+        .bind(&upload.tool.format_version)
+        .bind(&upload.tool.gcc_version)
+        .bind(&upload.labels)
+        .bind(&upload.commit)
+        .bind(upload.insert_time)
+        .bind(upload.pr_number)
+        .bind(&upload.build_url)
+        .bind(&upload.branch)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Counted with the new summary in place, so an error rolls it back
+        if let Some(quota) = &quota {
+            quota::enforce(&mut tx, org, quota).await?;
+        }
+
+        // The upload may be backdated, so pick the latest from the history rather than assuming
+        // it's this one. Replacing a commit's summary can move the latest backwards, which only a
+        // full refresh handles.
+        if replaced {
+            refresh_latest(&mut tx, org, repo).await?;
+        } else {
+            advance_latest(&mut tx, org, repo).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Inserted {
+            insert_time,
+            previous,
+            replaced,
+        })
+    }
+
+    /// Most summaries `insert_batch` can insert at once, as each binds 11 parameters and Postgres
+    /// allows at most 65535 in a query
+    pub const MAX_BATCH_ROWS: usize = u16::MAX as usize / 11;
+
+    /// Inserts several summaries with a single multi-row `INSERT`, e.g. those buffered by the batch
+    /// writer. Summaries without an `insert_time` are stored at the current time. If they'd take an
+    /// org over its quota nothing is stored, so each can be retried on its own.
+    pub async fn insert_batch(
+        db: &PgPool,
+        entries: &[(String, String, SummaryUpload)],
     ) -> Result<(), DbError> {
-        let json_coverage = serde_json::to_value(coverage)?;
+        // A multi-row upsert can't touch the same row twice, so only the last summary of each commit
+        // is kept, just as if they had been inserted one at a time
+        let mut seen_commits = HashSet::new();
+        let mut rows = Vec::new();
+        for (org, repo, upload) in entries.iter().rev() {
+            let duplicate = upload
+                .commit
+                .as_ref()
+                .is_some_and(|commit| !seen_commits.insert((org, repo, commit)));
+            if !duplicate {
+                let json_coverage = serde_json::to_value(upload.coverage.recomputed())?;
+                rows.push((org, repo, upload, json_coverage));
+            }
+        }
+        rows.reverse();
 
-        let _resp = sqlx::query("INSERT INTO summary VALUES (now(), $1, $2, $3)")
-            .bind(org)
-            .bind(repo)
-            .bind(json_coverage)
-            .execute(db)
-            .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder =
+            QueryBuilder::<Postgres>::new(format!("INSERT INTO summary ({ENTRY_COLUMNS}) "));
+        builder.push_values(&rows, |mut row, (org, repo, upload, json_coverage)| {
+            row.push("COALESCE(")
+                .push_bind_unseparated(upload.insert_time)
+                .push_unseparated(", now())")
+                .push_bind(org.as_str())
+                .push_bind(repo.as_str())
+                .push_bind(json_coverage)
+                .push_bind(&upload.tool.format_version)
+                .push_bind(&upload.tool.gcc_version)
+                .push_bind(&upload.labels)
+                .push_bind(&upload.commit)
+                .push_bind(upload.pr_number)
+                .push_bind(&upload.build_url)
+                .push_bind(&upload.branch);
+        });
+        builder.push(
+            r#" ON CONFLICT (org, repo, commit) WHERE commit IS NOT NULL DO UPDATE SET
+                insert_time = excluded.insert_time,
+                coverage = excluded.coverage,
+                format_version = excluded.format_version,
+                gcc_version = excluded.gcc_version,
+                labels = excluded.labels,
+                pr_number = excluded.pr_number,
+                build_url = excluded.build_url,
+                branch = excluded.branch
+            RETURNING org, repo, xmax <> 0"#,
+        );
+
+        let mut tx = db.begin().await?;
+        // Nobody is waiting on the batch, and cancelling it would drop every summary in it
+        disable_statement_timeout(&mut tx).await?;
+
+        // Locked in a consistent order so concurrent batches can't deadlock on each other's orgs
+        let orgs: BTreeSet<&str> = rows.iter().map(|(org, ..)| org.as_str()).collect();
+        let mut quotas = Vec::new();
+        for org in orgs {
+            if let Some(quota) = quota::lock(&mut tx, org).await? {
+                quotas.push((org, quota));
+            }
+        }
+
+        let inserted: Vec<(String, String, bool)> =
+            builder.build_query_as().fetch_all(&mut *tx).await?;
+
+        for (org, quota) in &quotas {
+            quota::enforce(&mut tx, org, quota).await?;
+        }
+
+        // As in `insert_into_table`, only repos with a replaced summary need a full refresh
+        let mut repos = HashMap::new();
+        for (org, repo, replaced) in inserted {
+            *repos.entry((org, repo)).or_insert(false) |= replaced;
+        }
+        for ((org, repo), replaced) in repos {
+            if replaced {
+                refresh_latest(&mut tx, &org, &repo).await?;
+            } else {
+                advance_latest(&mut tx, &org, &repo).await?;
+            }
+        }
+
+        tx.commit().await?;
 
         Ok(())
     }
 
-    /// Fetches the summary table
-    pub async fn fetch_table(db: &PgPool) -> Result<Vec<SummaryTableEntry>, DbError> {
-        let resp: Vec<SummaryTableEntry> = sqlx::query_as(
-            "SELECT DISTINCT org, repo, coverage, MAX(inserttime) AS inserttime FROM summary GROUP BY org, repo, coverage",
+    /// Deletes the oldest summaries of every repo in `org` until at most `keep` remain, returning how
+    /// many were deleted. Runs on a connection so it's part of the caller's transaction, e.g. the one
+    /// inserting the summary that went over the quota.
+    pub async fn prune_org(conn: &mut PgConnection, org: &str, keep: i64) -> Result<u64, DbError> {
+        let pruned: Vec<(String, String)> = sqlx::query_as(
+            r#"DELETE FROM summary
+                WHERE id IN (
+                    SELECT id FROM summary
+                    WHERE org = $1
+                    ORDER BY insert_time DESC, id DESC
+                    OFFSET $2
+                )
+                RETURNING org, repo"#,
         )
-        .fetch_all(&*db)
+        .bind(org)
+        .bind(keep.max(0))
+        .fetch_all(&mut *conn)
         .await?;
 
+        // A repo may have lost every summary, including its latest
+        let repos: HashSet<_> = pruned.iter().collect();
+        for (org, repo) in repos {
+            refresh_latest(conn, org, repo).await?;
+        }
+
+        Ok(pruned.len() as u64)
+    }
+
+    /// Fields the summary table can be sorted by
+    #[derive(Deserialize, Clone, Copy, Debug)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SortField {
+        LinePercent,
+        BranchPercent,
+        FunctionPercent,
+        InsertTime,
+        Repo,
+    }
+
+    impl SortField {
+        /// SQL expression to order by. Only these fixed expressions ever end up in the ORDER BY clause.
+        fn sql(self) -> &'static str {
+            match self {
+                SortField::LinePercent => "(coverage->>'line_percent')::double precision",
+                SortField::BranchPercent => "(coverage->>'branch_percent')::double precision",
+                SortField::FunctionPercent => "(coverage->>'function_percent')::double precision",
+                SortField::InsertTime => "insert_time",
+                SortField::Repo => "repo",
+            }
+        }
+    }
+
+    /// Periods a history can be downsampled to
+    #[derive(Deserialize, Clone, Copy, Debug)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Bucket {
+        Day,
+        Week,
+        Month,
+    }
+
+    impl Bucket {
+        /// `date_trunc` unit. Only these fixed units ever end up in the query.
+        fn sql(self) -> &'static str {
+            match self {
+                Bucket::Day => "day",
+                Bucket::Week => "week",
+                Bucket::Month => "month",
+            }
+        }
+    }
+
+    /// Direction to sort in
+    #[derive(Deserialize, Clone, Copy, Default, Debug)]
+    #[serde(rename_all = "lowercase")]
+    pub enum SortDir {
+        #[default]
+        Asc,
+        Desc,
+    }
+
+    impl SortDir {
+        fn sql(self) -> &'static str {
+            match self {
+                SortDir::Asc => "ASC",
+                SortDir::Desc => "DESC",
+            }
+        }
+    }
+
+    /// Options for filtering and ordering `fetch_table`
+    #[derive(Default)]
+    pub struct TableOptions<'a> {
+        /// Only consider summaries tagged with this label
+        pub label: Option<&'a str>,
+        /// Field to sort by, before falling back to org then repo
+        pub sort: Option<SortField>,
+        pub dir: SortDir,
+        /// Only include repos with at least this many summaries in their history
+        pub min_uploads: Option<i64>,
+    }
+
+    /// Fetches the latest summary of each repo in the summary table
+    pub async fn fetch_table(
+        db: &PgPool,
+        options: &TableOptions<'_>,
+    ) -> Result<Vec<SummaryTableEntry>, DbError> {
+        let order = match options.sort {
+            Some(field) => format!("{} {}, org, repo", field.sql(), options.dir.sql()),
+            None => "org, repo".to_string(),
+        };
+
+        // The latest summary with a label isn't necessarily the latest summary overall, so a label
+        // filter still has to search the history
+        let source = match options.label {
+            Some(_) => format!(
+                r#"(SELECT DISTINCT ON (org, repo) {ENTRY_COLUMNS}
+                    FROM summary
+                    WHERE labels @> ARRAY[$1::text]
+                    ORDER BY org, repo, insert_time DESC)"#
+            ),
+            None => "latest_summary".to_string(),
+        };
+
+        // Placeholders are only numbered for the filters actually given
+        let min_uploads_param = if options.label.is_some() { "$2" } else { "$1" };
+        let filter = match options.min_uploads {
+            Some(_) => format!(
+                r#"WHERE (org, repo) IN (
+                    SELECT org, repo FROM summary
+                    GROUP BY org, repo
+                    HAVING count(*) >= {min_uploads_param})"#
+            ),
+            None => String::new(),
+        };
+
+        let query =
+            format!("SELECT {ENTRY_COLUMNS} FROM {source} latest {filter} ORDER BY {order}");
+
+        let mut query = sqlx::query_as(&query);
+        if let Some(label) = options.label {
+            query = query.bind(label);
+        }
+        if let Some(min_uploads) = options.min_uploads {
+            query = query.bind(min_uploads);
+        }
+
+        let resp: Vec<SummaryTableEntry> = log_if_slow("fetch_table", query.fetch_all(db)).await?;
+
         Ok(resp)
     }
+
+    /// Fetches the latest summary of `org`/`repo`, if it has any
+    pub async fn fetch_latest(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+    ) -> Result<Option<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT {ENTRY_COLUMNS}
+                FROM latest_summary
+                WHERE org = $1 AND repo = $2"#
+        );
+
+        let resp: Option<SummaryTableEntry> = log_if_slow(
+            "fetch_latest",
+            sqlx::query_as(&query)
+                .bind(org)
+                .bind(repo)
+                .fetch_optional(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the latest summary of `org`/`repo` produced for pull request `pr_number`, if it has any
+    pub async fn fetch_latest_for_pr(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        pr_number: i32,
+    ) -> Result<Option<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org = $1 AND repo = $2 AND pr_number = $3
+                ORDER BY insert_time DESC
+                LIMIT 1"#
+        );
+
+        let resp: Option<SummaryTableEntry> = log_if_slow(
+            "fetch_latest_for_pr",
+            sqlx::query_as(&query)
+                .bind(org)
+                .bind(repo)
+                .bind(pr_number)
+                .fetch_optional(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the latest summary of each branch of `org`/`repo`, ordered by branch. Summaries
+    /// uploaded without a branch are left out.
+    pub async fn fetch_latest_per_branch(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+    ) -> Result<Vec<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT DISTINCT ON (branch) {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org = $1 AND repo = $2 AND branch IS NOT NULL
+                ORDER BY branch, insert_time DESC"#
+        );
+
+        let resp: Vec<SummaryTableEntry> = log_if_slow(
+            "fetch_latest_per_branch",
+            sqlx::query_as(&query).bind(org).bind(repo).fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches every summary of `org`/`repo`, oldest first
+    pub async fn fetch_history(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+    ) -> Result<Vec<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT {ENTRY_COLUMNS}
+                FROM summary
+                WHERE org = $1 AND repo = $2
+                ORDER BY insert_time"#
+        );
+
+        let resp: Vec<SummaryTableEntry> = log_if_slow(
+            "fetch_history",
+            sqlx::query_as(&query).bind(org).bind(repo).fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the last summary of `org`/`repo` in each `bucket` of time, oldest first, to keep long
+    /// histories down to a readable number of points
+    pub async fn fetch_history_bucketed(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        bucket: Bucket,
+    ) -> Result<Vec<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT {ENTRY_COLUMNS} FROM (
+                    SELECT DISTINCT ON (date_trunc('{unit}', insert_time AT TIME ZONE 'UTC')) {ENTRY_COLUMNS}
+                    FROM summary
+                    WHERE org = $1 AND repo = $2
+                    ORDER BY date_trunc('{unit}', insert_time AT TIME ZONE 'UTC'), insert_time DESC
+                ) AS last_per_bucket
+                ORDER BY insert_time"#,
+            unit = bucket.sql()
+        );
+
+        let resp: Vec<SummaryTableEntry> = log_if_slow(
+            "fetch_history_bucketed",
+            sqlx::query_as(&query).bind(org).bind(repo).fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the commits that `org`/`repo` has summaries for, newest first
+    pub async fn fetch_commits(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+    ) -> Result<Vec<CommitEntry>, DbError> {
+        let resp: Vec<CommitEntry> = log_if_slow(
+            "fetch_commits",
+            sqlx::query_as(
+                r#"SELECT commit, floor(extract(epoch FROM insert_time))::bigint AS insert_time
+                FROM summary
+                WHERE org = $1 AND repo = $2 AND commit IS NOT NULL
+                ORDER BY summary.insert_time DESC"#,
+            )
+            .bind(org)
+            .bind(repo)
+            .fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the latest `count` summaries of each repo in `org`, ordered by repo then newest first
+    pub async fn fetch_org_recent(
+        db: &PgPool,
+        org: &str,
+        count: i64,
+    ) -> Result<Vec<SummaryTableEntry>, DbError> {
+        let query = format!(
+            r#"SELECT {ENTRY_COLUMNS} FROM (
+                    SELECT *, row_number() OVER (PARTITION BY repo ORDER BY insert_time DESC) AS rn
+                    FROM summary
+                    WHERE org = $1
+                ) recent
+                WHERE rn <= $2
+                ORDER BY repo, insert_time DESC"#
+        );
+
+        let resp: Vec<SummaryTableEntry> = log_if_slow(
+            "fetch_org_recent",
+            sqlx::query_as(&query).bind(org).bind(count).fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// An org's repo count and when any of its repos last had a summary stored
+    #[derive(Serialize, sqlx::FromRow, Debug)]
+    pub struct OrgOverview {
+        pub org: String,
+        /// Number of repos in the org with at least one summary
+        pub repos: i64,
+        /// Unix timestamp of the org's most recent summary
+        pub last_updated: i64,
+    }
+
+    /// Fetches an overview of every org, most recently active first
+    pub async fn fetch_org_overview(db: &PgPool) -> Result<Vec<OrgOverview>, DbError> {
+        let resp: Vec<OrgOverview> = log_if_slow(
+            "fetch_org_overview",
+            sqlx::query_as(
+                r#"SELECT org,
+                    count(*) AS repos,
+                    floor(extract(epoch FROM max(insert_time)))::bigint AS last_updated
+                FROM latest_summary
+                GROUP BY org
+                ORDER BY max(insert_time) DESC, org"#,
+            )
+            .fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Streams every summary of every repo, oldest first, without holding them all in memory. It
+    /// runs on `conn` so the caller can lift the statement timeout first, which a long export outlives.
+    pub fn stream_all(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<SummaryTableEntry, sqlx::Error>> {
+        sqlx::query_as(&STREAM_ALL_QUERY).fetch(conn)
+    }
+
+    /// Fetches up to `limit` summaries of every repo, newest first, starting after `after`. Paging by
+    /// keyset rather than offset means summaries stored between pages don't shift later pages.
+    pub async fn fetch_page(
+        db: &PgPool,
+        after: Option<Cursor>,
+        limit: i64,
+    ) -> Result<Vec<NumberedEntry>, DbError> {
+        let query = format!(
+            r#"SELECT id, {ENTRY_COLUMNS}
+                FROM summary
+                WHERE $1::timestamptz IS NULL OR (insert_time, id) < ($1, $2)
+                ORDER BY insert_time DESC, id DESC
+                LIMIT $3"#
+        );
+
+        let resp: Vec<NumberedEntry> = log_if_slow(
+            "fetch_page",
+            sqlx::query_as(&query)
+                .bind(after.map(|c| c.insert_time))
+                .bind(after.map(|c| c.id))
+                .bind(limit)
+                .fetch_all(db),
+        )
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Checks whether any summary has been stored for `org`/`repo`
+    pub async fn repo_exists(db: &PgPool, org: &str, repo: &str) -> Result<bool, DbError> {
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT EXISTS (SELECT 1 FROM summary WHERE org = $1 AND repo = $2)")
+                .bind(org)
+                .bind(repo)
+                .fetch_one(db)
+                .await?;
+
+        Ok(exists)
+    }
+
+    /// Checks whether a summary of `commit` is stored for `org`/`repo`
+    pub async fn commit_exists(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        commit: &str,
+    ) -> Result<bool, DbError> {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM summary WHERE org = $1 AND repo = $2 AND commit = $3)",
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(commit)
+        .fetch_one(db)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Re-keys every summary of `from_org`/`from_repo` to `to_org`/`to_repo`
+    pub(super) async fn move_repo(
+        conn: &mut PgConnection,
+        from_org: &str,
+        from_repo: &str,
+        to_org: &str,
+        to_repo: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let resp =
+            sqlx::query("UPDATE summary SET org = $3, repo = $4 WHERE org = $1 AND repo = $2")
+                .bind(from_org)
+                .bind(from_repo)
+                .bind(to_org)
+                .bind(to_repo)
+                .execute(&mut *conn)
+                .await?;
+
+        refresh_latest(conn, from_org, from_repo).await?;
+        refresh_latest(conn, to_org, to_repo).await?;
+
+        Ok(resp.rows_affected())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn summary(counts: [(usize, usize); 3]) -> CoverageSummary {
+            let [branch, function, line] = counts.map(|(c, t)| Coverage::from_counts(c, t));
+            CoverageSummary {
+                branch,
+                function,
+                line,
+                extra: BTreeMap::new(),
+            }
+        }
+
+        // These assume `PERCENT_DECIMALS` isn't set, leaving the default of 2
+        #[test]
+        fn rounds_percents() {
+            assert_eq!(round_percent(83.33333), 83.33);
+            assert_eq!(round_percent(66.666), 66.67);
+            assert_eq!(round_percent(100.0), 100.0);
+            assert_eq!(round_percent(0.004), 0.0);
+        }
+
+        #[test]
+        fn rounds_stored_percents() {
+            let coverage = serde_json::json!({
+                "line_covered": 1,
+                "line_total": 3,
+                "line_percent": 33.333333,
+                "gcc_version": "13.2.0",
+                "extra": { "condition": { "covered": 2, "total": 3, "percent": 66.666666 } },
+            });
+
+            let rounded = round_coverage_percents(&coverage);
+            assert_eq!(rounded["line_percent"], 33.33);
+            assert_eq!(rounded["line_covered"], 1);
+            assert_eq!(rounded["gcc_version"], "13.2.0");
+            assert_eq!(rounded["extra"]["condition"]["percent"], 66.67);
+        }
+
+        #[test]
+        fn serializes_rounded_percents() {
+            let mut coverage = summary([(2, 3), (1, 6), (1, 3)]);
+            coverage
+                .extra
+                .insert("condition".to_string(), Coverage::from_counts(5, 7));
+            let entry = SummaryTableEntry {
+                insert_time: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                org: "org".to_string(),
+                repo: "repo".to_string(),
+                coverage: serde_json::to_value(&coverage).unwrap(),
+                tool: ToolInfo::default(),
+                labels: Vec::new(),
+                commit: None,
+                pr_number: None,
+                build_url: None,
+                branch: None,
+            };
+
+            let serialized = serde_json::to_value(&entry).unwrap();
+            assert_eq!(serialized["coverage"]["branch_percent"], 66.67);
+            assert_eq!(serialized["coverage"]["function_percent"], 16.67);
+            assert_eq!(serialized["coverage"]["line_percent"], 33.33);
+            assert_eq!(
+                serialized["coverage"]["extra"]["condition"]["percent"],
+                71.43
+            );
+            assert_eq!(serialized["insert_time"], 1_700_000_000);
+
+            let flat = serde_json::to_value(FlatSummary::try_from(&entry).unwrap()).unwrap();
+            assert_eq!(flat["branch_percent"], 66.67);
+            assert_eq!(flat["function_percent"], 16.67);
+            assert_eq!(flat["line_percent"], 33.33);
+            assert_eq!(flat["extra_percents"]["condition"], 71.43);
+
+            // The raw entry keeps the exact percentages
+            assert_eq!(
+                entry.raw()["coverage"]["line_percent"],
+                coverage.line.percent
+            );
+        }
+
+        #[test]
+        fn cursor_round_trips() {
+            let cursor = Cursor {
+                insert_time: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+                id: 42,
+            };
+
+            let decoded = Cursor::decode(&cursor.encode()).unwrap();
+            assert_eq!(decoded.insert_time, cursor.insert_time);
+            assert_eq!(decoded.id, 42);
+        }
+
+        #[test]
+        fn rejects_malformed_cursors() {
+            for keyset in ["", "123", "abc:1", "123:abc", "123:1:2"] {
+                let token = URL_SAFE_NO_PAD.encode(keyset);
+                assert!(Cursor::decode(&token).is_none(), "{keyset:?}");
+            }
+            assert!(Cursor::decode("not base64!").is_none());
+            assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode([0xff, b':', b'1'])).is_none());
+        }
+
+        #[test]
+        fn uploads_keep_tool_info() {
+            let upload: SummaryUpload = serde_json::from_value(serde_json::json!({
+                "branch_covered": 1, "branch_total": 2, "branch_percent": 50.0,
+                "function_covered": 3, "function_total": 4, "function_percent": 75.0,
+                "line_covered": 5, "line_total": 10, "line_percent": 50.0,
+                "format_version": "2",
+                "gcc_version": "14.1.0",
+            }))
+            .unwrap();
+
+            assert_eq!(upload.tool.format_version.as_deref(), Some("2"));
+            assert_eq!(upload.tool.gcc_version.as_deref(), Some("14.1.0"));
+            assert_eq!(upload.coverage.line.total, 10);
+            assert!(upload.coverage.extra.is_empty());
+        }
+
+        #[test]
+        fn uploads_from_older_gcov_lack_tool_info() {
+            let upload: SummaryUpload = serde_json::from_value(serde_json::json!({
+                "branch_covered": 1, "branch_total": 2, "branch_percent": 50.0,
+                "function_covered": 3, "function_total": 4, "function_percent": 75.0,
+                "line_covered": 5, "line_total": 10, "line_percent": 50.0,
+            }))
+            .unwrap();
+
+            assert!(upload.tool.format_version.is_none());
+            assert!(upload.tool.gcc_version.is_none());
+        }
+
+        #[test]
+        fn merge_sums_counts() {
+            let mut a = summary([(1, 2), (3, 4), (5, 10)]);
+            a.extra
+                .insert("condition".to_string(), Coverage::from_counts(1, 4));
+            let b = summary([(1, 2), (1, 4), (5, 10)]);
+
+            let merged = CoverageSummary::merge(&[a, b]).unwrap();
+            assert_eq!((merged.branch.covered, merged.branch.total), (2, 4));
+            assert_eq!((merged.function.covered, merged.function.total), (4, 8));
+            assert_eq!((merged.line.covered, merged.line.total), (10, 20));
+            assert_eq!(merged.line.percent, 50.0);
+            assert_eq!(merged.extra["condition"].total, 4);
+        }
+
+        #[test]
+        fn merging_nothing_is_empty() {
+            let merged = CoverageSummary::merge(&[]).unwrap();
+            assert_eq!(merged.line.total, 0);
+            assert_eq!(merged.line.percent, 0.0);
+            assert!(merged.extra.is_empty());
+        }
+
+        fn upload(counts: [(usize, usize); 3]) -> SummaryUpload {
+            SummaryUpload {
+                coverage: summary(counts),
+                tool: ToolInfo::default(),
+                labels: Vec::new(),
+                commit: None,
+                pr_number: None,
+                build_url: None,
+                branch: None,
+                insert_time: None,
+            }
+        }
+
+        #[sqlx::test]
+        async fn fetches_inserted_summaries(db: PgPool) {
+            crate::test_util::setup(&db).await;
+
+            insert_into_table(&db, "org", "a", &upload([(1, 2), (3, 4), (5, 10)]))
+                .await
+                .unwrap();
+            insert_into_table(&db, "org", "b", &upload([(1, 2), (3, 4), (1, 10)]))
+                .await
+                .unwrap();
+            insert_into_table(&db, "org", "a", &upload([(1, 2), (3, 4), (7, 10)]))
+                .await
+                .unwrap();
+
+            let table = fetch_table(&db, &TableOptions::default()).await.unwrap();
+            let repos: Vec<_> = table
+                .iter()
+                .map(|e| (e.repo.as_str(), e.coverage_summary().unwrap().line.covered))
+                .collect();
+            assert_eq!(repos, [("a", 7), ("b", 1)]);
+
+            let latest = fetch_latest(&db, "org", "a").await.unwrap().unwrap();
+            assert_eq!(latest.coverage_summary().unwrap().line.percent, 70.0);
+            assert!(fetch_latest(&db, "org", "c").await.unwrap().is_none());
+        }
+
+        #[sqlx::test]
+        async fn latest_follows_insert_time_rather_than_insert_order(db: PgPool) {
+            crate::test_util::setup(&db).await;
+            let at = |line_covered, secs| SummaryUpload {
+                insert_time: DateTime::from_timestamp(secs, 0),
+                ..upload([(1, 2), (3, 4), (line_covered, 10)])
+            };
+
+            insert_into_table(&db, "org", "a", &at(5, 2_000))
+                .await
+                .unwrap();
+            // Backfilled summaries from before the latest one leave it in place
+            insert_into_table(&db, "org", "a", &at(1, 1_000))
+                .await
+                .unwrap();
+            let latest = fetch_latest(&db, "org", "a").await.unwrap().unwrap();
+            assert_eq!(latest.coverage_summary().unwrap().line.covered, 5);
+
+            insert_into_table(&db, "org", "a", &at(8, 3_000))
+                .await
+                .unwrap();
+            insert_into_table(&db, "org", "a", &at(2, 2_500))
+                .await
+                .unwrap();
+
+            let table = fetch_table(&db, &TableOptions::default()).await.unwrap();
+            assert_eq!(table.len(), 1);
+            assert_eq!(table[0].coverage_summary().unwrap().line.covered, 8);
+            assert_eq!(table[0].insert_time.timestamp(), 3_000);
+        }
+
+        #[sqlx::test]
+        async fn concurrent_older_inserts_never_replace_a_newer_latest(db: PgPool) {
+            crate::test_util::setup(&db).await;
+            let at = |line_covered, secs| SummaryUpload {
+                insert_time: DateTime::from_timestamp(secs, 0),
+                ..upload([(1, 2), (3, 4), (line_covered, 10)])
+            };
+
+            let (first, second, third, backfilled) =
+                (at(1, 1_000), at(9, 2_000), at(7, 3_000), at(2, 1_500));
+
+            // Repeated across repos, as which insert gets in first varies from run to run
+            for i in 0..10 {
+                let repo = format!("repo-{i}");
+                let (older, newer) = tokio::join!(
+                    insert_into_table(&db, "org", &repo, &first),
+                    insert_into_table(&db, "org", &repo, &second),
+                );
+                older.unwrap();
+                newer.unwrap();
+
+                let latest = fetch_latest(&db, "org", &repo).await.unwrap().unwrap();
+                assert_eq!(latest.insert_time.timestamp(), 2_000, "{repo}");
+                assert_eq!(latest.coverage_summary().unwrap().line.covered, 9);
+            }
+
+            // A backfill racing a newer upload to a repo with history leaves the newer one too
+            let (newer, older) = tokio::join!(
+                insert_into_table(&db, "org", "repo-0", &third),
+                insert_into_table(&db, "org", "repo-0", &backfilled),
+            );
+            newer.unwrap();
+            older.unwrap();
+            let latest = fetch_latest(&db, "org", "repo-0").await.unwrap().unwrap();
+            assert_eq!(latest.insert_time.timestamp(), 3_000);
+            assert_eq!(fetch_history(&db, "org", "repo-0").await.unwrap().len(), 4);
+        }
+
+        #[sqlx::test]
+        async fn buckets_keep_the_last_summary_of_each_period(db: PgPool) {
+            crate::test_util::setup(&db).await;
+            // 2024-01-01 is a Monday
+            for (line_covered, time) in [
+                (1, "2024-01-01T09:00:00Z"),
+                (2, "2024-01-01T23:59:59Z"),
+                (3, "2024-01-01T12:00:00Z"),
+                (4, "2024-01-02T00:00:00Z"),
+                (5, "2024-01-08T10:00:00Z"),
+                (6, "2024-02-01T10:00:00Z"),
+            ] {
+                let upload = SummaryUpload {
+                    insert_time: Some(time.parse().unwrap()),
+                    ..upload([(1, 2), (3, 4), (line_covered, 10)])
+                };
+                insert_into_table(&db, "org", "repo", &upload)
+                    .await
+                    .unwrap();
+            }
+
+            let covered = |history: Vec<SummaryTableEntry>| -> Vec<usize> {
+                history
+                    .iter()
+                    .map(|e| e.coverage_summary().unwrap().line.covered)
+                    .collect()
+            };
+            let bucketed = |bucket| fetch_history_bucketed(&db, "org", "repo", bucket);
+
+            // Oldest first, with the same-day points collapsed into the day's last
+            assert_eq!(covered(bucketed(Bucket::Day).await.unwrap()), [2, 4, 5, 6]);
+            assert_eq!(covered(bucketed(Bucket::Week).await.unwrap()), [4, 5, 6]);
+            assert_eq!(covered(bucketed(Bucket::Month).await.unwrap()), [5, 6]);
+
+            let raw = fetch_history(&db, "org", "repo").await.unwrap();
+            assert_eq!(raw.len(), 6);
+        }
+
+        #[sqlx::test]
+        async fn pages_stay_put_when_summaries_arrive_between_them(db: PgPool) {
+            crate::test_util::setup(&db).await;
+            let insert = |repo: &'static str, secs| {
+                let db = db.clone();
+                async move {
+                    let upload = SummaryUpload {
+                        insert_time: DateTime::from_timestamp(secs, 0),
+                        ..upload([(1, 2), (3, 4), (5, 10)])
+                    };
+                    insert_into_table(&db, "org", repo, &upload).await.unwrap();
+                }
+            };
+            let repos = |page: &[NumberedEntry]| -> Vec<String> {
+                page.iter().map(|row| row.entry.repo.clone()).collect()
+            };
+
+            // `c` and `d` are stored at the same time, so the id orders them
+            for (repo, secs) in [("a", 1_000), ("b", 2_000), ("c", 3_000), ("d", 3_000)] {
+                insert(repo, secs).await;
+            }
+            insert("e", 4_000).await;
+
+            let first = fetch_page(&db, None, 2).await.unwrap();
+            assert_eq!(repos(&first), ["e", "d"]);
+
+            // Newer summaries don't shift the next page, while one backfilled past it joins it
+            insert("new", 5_000).await;
+            insert("backfilled", 1_500).await;
+
+            let cursor = Cursor::after(first.last().unwrap());
+            let second = fetch_page(&db, Some(cursor), 2).await.unwrap();
+            assert_eq!(repos(&second), ["c", "b"]);
+
+            let cursor = Cursor::after(second.last().unwrap());
+            let third = fetch_page(&db, Some(cursor), 2).await.unwrap();
+            assert_eq!(repos(&third), ["backfilled", "a"]);
+
+            let cursor = Cursor::after(third.last().unwrap());
+            assert!(fetch_page(&db, Some(cursor), 2).await.unwrap().is_empty());
+        }
+
+        #[test]
+        fn recomputes_percents_from_counts() {
+            let mut coverage = summary([(1, 2), (3, 4), (1, 3)]);
+            coverage.branch.percent = 99.0;
+            coverage.function.percent = -5.0;
+            coverage.line.percent = 250.0;
+            coverage.extra.insert(
+                "condition".to_string(),
+                Coverage {
+                    covered: 0,
+                    total: 0,
+                    percent: 42.0,
+                },
+            );
+
+            // Whatever the client claimed, only the counts matter
+            assert!(coverage.validate().is_ok());
+
+            let recomputed = coverage.recomputed();
+            assert_eq!(recomputed.branch.percent, 50.0);
+            assert_eq!(recomputed.function.percent, 75.0);
+            assert_eq!(recomputed.line.percent, 1.0 / 3.0 * 100.0);
+            assert_eq!(recomputed.extra["condition"].percent, 0.0);
+            assert_eq!((recomputed.line.covered, recomputed.line.total), (1, 3));
+        }
+
+        #[test]
+        fn merge_rejects_overflowing_counts() {
+            let big = summary([(0, usize::MAX), (0, 1), (0, 1)]);
+            assert!(CoverageSummary::merge(&[big.clone(), big.clone()]).is_none());
+
+            let mut a = summary([(0, 1); 3]);
+            a.extra.insert(
+                "mcdc".to_string(),
+                Coverage::from_counts(usize::MAX, usize::MAX),
+            );
+            assert!(CoverageSummary::merge(&[a.clone(), a]).is_none());
+        }
+    }
+}
+
+pub mod repo_config {
+    use crate::db::summary::CoverageSummary;
+    use crate::db::DbError;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{postgres::PgQueryResult, PgPool};
+
+    /// Represents a repo's stored coverage gate thresholds, as percentages
+    #[derive(Serialize, Deserialize, sqlx::FromRow, Default, Debug)]
+    pub struct RepoConfig {
+        pub line_threshold: Option<f64>,
+        pub branch_threshold: Option<f64>,
+        pub function_threshold: Option<f64>,
+    }
+
+    impl RepoConfig {
+        /// Lists every metric of `summary` that falls below its threshold by more than `tolerance`
+        /// percentage points
+        pub fn failures(&self, summary: &CoverageSummary, tolerance: f64) -> Vec<String> {
+            [
+                ("line", self.line_threshold, summary.line.percent),
+                ("branch", self.branch_threshold, summary.branch.percent),
+                (
+                    "function",
+                    self.function_threshold,
+                    summary.function.percent,
+                ),
+            ]
+            .into_iter()
+            .filter_map(|(name, threshold, percent)| match threshold {
+                Some(threshold) if percent < threshold - tolerance => {
+                    Some(format!("{name}: {percent} is below {threshold}"))
+                }
+                _ => None,
+            })
+            .collect()
+        }
+    }
+
+    /// Creates the repo_config db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS repo_config (
+                        org varchar,
+                        repo varchar,
+                        line_threshold double precision,
+                        branch_threshold double precision,
+                        function_threshold double precision,
+                        PRIMARY KEY (org, repo)
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Sets the config of `org`/`repo`, replacing any existing config
+    pub async fn upsert(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        config: &RepoConfig,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            r#"INSERT INTO repo_config (org, repo, line_threshold, branch_threshold, function_threshold)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (org, repo) DO UPDATE SET
+                    line_threshold = excluded.line_threshold,
+                    branch_threshold = excluded.branch_threshold,
+                    function_threshold = excluded.function_threshold"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(config.line_threshold)
+        .bind(config.branch_threshold)
+        .bind(config.function_threshold)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the config of `org`/`repo`, if it has one
+    pub async fn fetch(db: &PgPool, org: &str, repo: &str) -> Result<Option<RepoConfig>, DbError> {
+        let resp: Option<RepoConfig> = sqlx::query_as(
+            r#"SELECT line_threshold, branch_threshold, function_threshold
+                FROM repo_config WHERE org = $1 AND repo = $2"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod goals {
+    use crate::db::DbError;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{postgres::PgQueryResult, PgPool};
+
+    /// Represents a repo's aspirational coverage targets, as percentages
+    #[derive(Serialize, Deserialize, sqlx::FromRow, Default, Debug)]
+    pub struct CoverageGoals {
+        pub line_goal: Option<f64>,
+        pub branch_goal: Option<f64>,
+        pub function_goal: Option<f64>,
+    }
+
+    /// Creates the coverage_goals db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS coverage_goals (
+                        org varchar,
+                        repo varchar,
+                        line_goal double precision,
+                        branch_goal double precision,
+                        function_goal double precision,
+                        PRIMARY KEY (org, repo)
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Sets the goals of `org`/`repo`, replacing any existing goals
+    pub async fn upsert(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        goals: &CoverageGoals,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            r#"INSERT INTO coverage_goals (org, repo, line_goal, branch_goal, function_goal)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (org, repo) DO UPDATE SET
+                    line_goal = excluded.line_goal,
+                    branch_goal = excluded.branch_goal,
+                    function_goal = excluded.function_goal"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(goals.line_goal)
+        .bind(goals.branch_goal)
+        .bind(goals.function_goal)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the goals of `org`/`repo`, if it has any
+    pub async fn fetch(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+    ) -> Result<Option<CoverageGoals>, DbError> {
+        let resp: Option<CoverageGoals> = sqlx::query_as(
+            r#"SELECT line_goal, branch_goal, function_goal
+                FROM coverage_goals WHERE org = $1 AND repo = $2"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod annotations {
+    use crate::db::DbError;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{
+        postgres::PgQueryResult,
+        types::chrono::{DateTime, Utc},
+        PgPool,
+    };
+
+    /// Columns selected to build an `Annotation`, with the times as unix timestamps. They're rounded
+    /// down, as summaries' are, since casting would round to the nearest second instead.
+    const ANNOTATION_COLUMNS: &str = "id, \
+        floor(extract(epoch FROM summary_time))::bigint AS summary_time, \
+        author, text, \
+        floor(extract(epoch FROM created_at))::bigint AS created_at";
+
+    /// A note left on one of a repo's summaries, e.g. explaining a dip in coverage
+    #[derive(Serialize, sqlx::FromRow, Debug)]
+    pub struct Annotation {
+        pub id: i64,
+        /// Unix timestamp of the summary the note is attached to
+        pub summary_time: i64,
+        pub author: String,
+        pub text: String,
+        /// Unix timestamp the note was left at
+        pub created_at: i64,
+    }
+
+    /// The body of a request to add an annotation
+    #[derive(Deserialize)]
+    pub struct NewAnnotation {
+        pub author: String,
+        pub text: String,
+    }
+
+    /// Creates the annotations db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS annotations (
+                        id bigserial PRIMARY KEY,
+                        org varchar NOT NULL,
+                        repo varchar NOT NULL,
+                        summary_time timestamptz NOT NULL,
+                        author varchar NOT NULL,
+                        text text NOT NULL,
+                        created_at timestamptz NOT NULL DEFAULT now()
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Attaches an annotation to the summary of `org`/`repo` stored at `summary_time`
+    pub async fn insert(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        summary_time: DateTime<Utc>,
+        annotation: &NewAnnotation,
+    ) -> Result<Annotation, DbError> {
+        let query = format!(
+            r#"INSERT INTO annotations (org, repo, summary_time, author, text)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING {ANNOTATION_COLUMNS}"#
+        );
+
+        let resp: Annotation = sqlx::query_as(&query)
+            .bind(org)
+            .bind(repo)
+            .bind(summary_time)
+            .bind(&annotation.author)
+            .bind(&annotation.text)
+            .fetch_one(db)
+            .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches every annotation of `org`/`repo`, oldest first
+    pub async fn fetch(db: &PgPool, org: &str, repo: &str) -> Result<Vec<Annotation>, DbError> {
+        let query = format!(
+            r#"SELECT {ANNOTATION_COLUMNS}
+                FROM annotations
+                WHERE org = $1 AND repo = $2
+                ORDER BY annotations.created_at, id"#
+        );
+
+        let resp: Vec<Annotation> = sqlx::query_as(&query)
+            .bind(org)
+            .bind(repo)
+            .fetch_all(db)
+            .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod failures {
+    use crate::db::DbError;
+    use lazy_static::lazy_static;
+    use serde::Serialize;
+    use sqlx::{postgres::PgQueryResult, PgPool};
+
+    const DEFAULT_MAX_FAILED_UPLOADS: i64 = 1000;
+
+    lazy_static! {
+        /// Most failed uploads kept, the oldest being deleted as new ones are recorded
+        static ref MAX_FAILED_UPLOADS: i64 =
+            crate::parse_env_var_or("MAX_FAILED_UPLOADS", DEFAULT_MAX_FAILED_UPLOADS).max(1);
+    }
+
+    /// An upload that was rejected or couldn't be stored
+    #[derive(Serialize, sqlx::FromRow, Debug)]
+    pub struct FailedUpload {
+        pub id: i64,
+        /// Unix timestamp the upload failed at
+        pub failed_at: i64,
+        pub org: String,
+        pub repo: String,
+        /// Why the upload failed, as reported to the client
+        pub error: String,
+        /// The start of the uploaded summary
+        pub payload: String,
+    }
+
+    /// Creates the failed_uploads db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS failed_uploads (
+                        id bigserial PRIMARY KEY,
+                        failed_at timestamptz NOT NULL DEFAULT now(),
+                        org varchar NOT NULL,
+                        repo varchar NOT NULL,
+                        error text NOT NULL,
+                        payload text NOT NULL
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Records a failed upload to `org`/`repo`, deleting the oldest beyond `MAX_FAILED_UPLOADS`
+    pub async fn insert(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        error: &str,
+        payload: &str,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            "INSERT INTO failed_uploads (org, repo, error, payload) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(error)
+        .bind(payload)
+        .execute(db)
+        .await?;
+
+        sqlx::query(
+            r#"DELETE FROM failed_uploads
+                WHERE id <= (SELECT id FROM failed_uploads ORDER BY id DESC OFFSET $1 LIMIT 1)"#,
+        )
+        .bind(*MAX_FAILED_UPLOADS)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the `count` most recent failed uploads, newest first
+    pub async fn fetch_recent(db: &PgPool, count: i64) -> Result<Vec<FailedUpload>, DbError> {
+        let resp: Vec<FailedUpload> = sqlx::query_as(
+            r#"SELECT id, floor(extract(epoch FROM failed_at))::bigint AS failed_at, org, repo, error, payload
+                FROM failed_uploads
+                ORDER BY failed_uploads.failed_at DESC, id DESC
+                LIMIT $1"#,
+        )
+        .bind(count)
+        .fetch_all(db)
+        .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod baselines {
+    use crate::db::summary::CoverageSummary;
+    use crate::db::DbError;
+    use serde::Deserialize;
+    use sqlx::{
+        postgres::PgQueryResult,
+        types::chrono::{DateTime, Utc},
+        PgPool,
+    };
+
+    /// A copy of one of a repo's summaries kept under a name, e.g. "release-1.0", to compare later
+    /// coverage against. It's a copy so replacing or moving the summary doesn't change it.
+    #[derive(sqlx::FromRow, Debug)]
+    pub struct Baseline {
+        /// Time the copied summary was stored at
+        pub summary_time: DateTime<Utc>,
+        pub coverage: sqlx::types::JsonValue,
+    }
+
+    impl Baseline {
+        pub fn coverage_summary(&self) -> Result<CoverageSummary, serde_json::Error> {
+            CoverageSummary::deserialize(&self.coverage)
+        }
+    }
+
+    /// Creates the baselines db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS baselines (
+                        org varchar,
+                        repo varchar,
+                        name varchar,
+                        summary_time timestamptz NOT NULL,
+                        coverage jsonb NOT NULL,
+                        PRIMARY KEY (org, repo, name)
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Marks the latest summary of `org`/`repo` as the baseline `name`, replacing any existing
+    /// baseline of that name. Returns the marked summary's time, or `None` if the repo has no
+    /// summaries.
+    pub async fn set_to_latest(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        name: &str,
+    ) -> Result<Option<DateTime<Utc>>, DbError> {
+        let resp: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"INSERT INTO baselines (org, repo, name, summary_time, coverage)
+                SELECT org, repo, $3, insert_time, coverage
+                FROM latest_summary
+                WHERE org = $1 AND repo = $2
+                ON CONFLICT (org, repo, name) DO UPDATE SET
+                    summary_time = excluded.summary_time,
+                    coverage = excluded.coverage
+                RETURNING summary_time"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(name)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Fetches the baseline `name` of `org`/`repo`, if there is one
+    pub async fn fetch(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        name: &str,
+    ) -> Result<Option<Baseline>, DbError> {
+        let resp: Option<Baseline> = sqlx::query_as(
+            r#"SELECT summary_time, coverage
+                FROM baselines WHERE org = $1 AND repo = $2 AND name = $3"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(name)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod quota {
+    use crate::db::DbError;
+    use serde::{Deserialize, Serialize};
+    use sqlx::{postgres::PgQueryResult, PgConnection, PgPool};
+
+    /// Limits on how much an org may upload and store. Any limit left as `None` is unlimited.
+    #[derive(Serialize, Deserialize, sqlx::FromRow, Default, Debug)]
+    pub struct OrgQuota {
+        /// The most summaries the org may store across all its repos
+        pub max_summaries: Option<i64>,
+        /// The most summaries the org may upload in any hour
+        pub max_uploads_per_hour: Option<i64>,
+        /// Delete the org's oldest summaries to make room rather than rejecting uploads once
+        /// `max_summaries` is reached
+        #[serde(default)]
+        pub prune_oldest: bool,
+    }
+
+    /// Which of its org's limits an upload would exceed
+    #[derive(Debug)]
+    pub enum QuotaExceeded {
+        /// The org may only upload this many summaries an hour
+        Rate(i64),
+        /// The org may only store this many summaries
+        Storage(i64),
+    }
+
+    /// Creates the org_quota db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS org_quota (
+                        org varchar PRIMARY KEY,
+                        max_summaries bigint,
+                        max_uploads_per_hour bigint,
+                        prune_oldest boolean NOT NULL DEFAULT false
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Sets the quota of `org`, replacing any existing quota
+    pub async fn upsert(db: &PgPool, org: &str, quota: &OrgQuota) -> Result<(), DbError> {
+        sqlx::query(
+            r#"INSERT INTO org_quota (org, max_summaries, max_uploads_per_hour, prune_oldest)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (org) DO UPDATE SET
+                    max_summaries = excluded.max_summaries,
+                    max_uploads_per_hour = excluded.max_uploads_per_hour,
+                    prune_oldest = excluded.prune_oldest"#,
+        )
+        .bind(org)
+        .bind(quota.max_summaries)
+        .bind(quota.max_uploads_per_hour)
+        .bind(quota.prune_oldest)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the quota of `org`, if it has one
+    pub async fn fetch(db: &PgPool, org: &str) -> Result<Option<OrgQuota>, DbError> {
+        let resp: Option<OrgQuota> = sqlx::query_as(
+            r#"SELECT max_summaries, max_uploads_per_hour, prune_oldest
+                FROM org_quota WHERE org = $1"#,
+        )
+        .bind(org)
+        .fetch_optional(db)
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Counts the summaries `org` stored in the last hour and in total
+    async fn count_summaries(conn: &mut PgConnection, org: &str) -> Result<(i64, i64), DbError> {
+        let counts = sqlx::query_as(
+            r#"SELECT count(*) FILTER (WHERE insert_time > now() - interval '1 hour'), count(*)
+                FROM summary WHERE org = $1"#,
+        )
+        .bind(org)
+        .fetch_one(conn)
+        .await?;
+
+        Ok(counts)
+    }
+
+    /// Checks whether `org` may store another summary without changing anything, e.g. to turn away
+    /// a batched upload before acknowledging it. Orgs without a quota are unlimited. The quota is
+    /// only enforced when the summary is inserted, see `enforce`.
+    pub async fn check(db: &PgPool, org: &str) -> Result<Option<QuotaExceeded>, DbError> {
+        let Some(quota) = fetch(db, org).await? else {
+            return Ok(None);
+        };
+        let (recent, stored) = count_summaries(&mut *db.acquire().await?, org).await?;
+
+        Ok(match (quota.max_uploads_per_hour, quota.max_summaries) {
+            (Some(max), _) if recent >= max => Some(QuotaExceeded::Rate(max)),
+            (_, Some(max)) if stored >= max && !quota.prune_oldest => {
+                Some(QuotaExceeded::Storage(max))
+            }
+            _ => None,
+        })
+    }
+
+    /// Fetches the quota of `org` and locks it until the end of the transaction, so concurrent
+    /// uploads to the org are counted one after another. Must be called before touching any
+    /// summaries, as pruning touches every repo of the org.
+    pub(in crate::db) async fn lock(
+        conn: &mut PgConnection,
+        org: &str,
+    ) -> Result<Option<OrgQuota>, DbError> {
+        let resp: Option<OrgQuota> = sqlx::query_as(
+            r#"SELECT max_summaries, max_uploads_per_hour, prune_oldest
+                FROM org_quota WHERE org = $1 FOR UPDATE"#,
+        )
+        .bind(org)
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(resp)
+    }
+
+    /// Applies `quota` to `org` once its new summaries are inserted in the same transaction,
+    /// failing with `DbError::QuotaExceeded` so the transaction is rolled back. When the quota
+    /// allows it, the org's oldest summaries are pruned to make room instead.
+    pub(in crate::db) async fn enforce(
+        conn: &mut PgConnection,
+        org: &str,
+        quota: &OrgQuota,
+    ) -> Result<(), DbError> {
+        let (recent, stored) = count_summaries(conn, org).await?;
+
+        if let Some(max) = quota.max_uploads_per_hour.filter(|max| recent > *max) {
+            return Err(DbError::QuotaExceeded(QuotaExceeded::Rate(max)));
+        }
+
+        if let Some(max) = quota.max_summaries.filter(|max| stored > *max) {
+            if !quota.prune_oldest {
+                return Err(DbError::QuotaExceeded(QuotaExceeded::Storage(max)));
+            }
+
+            let pruned = crate::db::summary::prune_org(conn, org, max).await?;
+            tracing::info!(
+                "Pruned {} summaries of {} to stay within its quota",
+                pruned,
+                org
+            );
+        }
+
+        Ok(())
+    }
+}
+
+pub mod repo_secrets {
+    use crate::db::DbError;
+    use sqlx::{postgres::PgQueryResult, PgPool};
+
+    /// Creates the repo_secrets db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS repo_secrets (
+                        org varchar,
+                        repo varchar,
+                        secret varchar NOT NULL,
+                        PRIMARY KEY (org, repo)
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Sets the secret uploads to `org`/`repo` must be signed with, replacing any existing secret
+    pub async fn upsert(db: &PgPool, org: &str, repo: &str, secret: &str) -> Result<(), DbError> {
+        sqlx::query(
+            r#"INSERT INTO repo_secrets (org, repo, secret)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (org, repo) DO UPDATE SET secret = excluded.secret"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(secret)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the secret of `org`/`repo`, so its uploads no longer need signing. Returns whether
+    /// it had one.
+    pub async fn delete(db: &PgPool, org: &str, repo: &str) -> Result<bool, DbError> {
+        let resp = sqlx::query("DELETE FROM repo_secrets WHERE org = $1 AND repo = $2")
+            .bind(org)
+            .bind(repo)
+            .execute(db)
+            .await?;
+
+        Ok(resp.rows_affected() > 0)
+    }
+
+    /// Fetches the secret of `org`/`repo`, if it has one
+    pub async fn fetch(db: &PgPool, org: &str, repo: &str) -> Result<Option<String>, DbError> {
+        let resp: Option<String> =
+            sqlx::query_scalar("SELECT secret FROM repo_secrets WHERE org = $1 AND repo = $2")
+                .bind(org)
+                .bind(repo)
+                .fetch_optional(db)
+                .await?;
+
+        Ok(resp)
+    }
+}
+
+pub mod repo_info {
+    use crate::db::DbError;
+    use serde::Deserialize;
+    use sqlx::{postgres::PgQueryResult, PgPool};
+
+    /// Represents the details of a repo shown alongside its coverage
+    #[derive(Deserialize, sqlx::FromRow, Debug)]
+    pub struct RepoInfo {
+        /// A short description of the repo, as markdown
+        pub description: String,
+    }
+
+    /// Creates the repo_info db table if it doesn't exist
+    pub(in crate::db) async fn setup_table(db: &PgPool) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query(
+            r#"CREATE TABLE IF NOT EXISTS repo_info (
+                        org varchar,
+                        repo varchar,
+                        description text NOT NULL,
+                        PRIMARY KEY (org, repo)
+                    );"#,
+        )
+        .execute(db)
+        .await
+    }
+
+    /// Sets the info of `org`/`repo`, replacing any existing info
+    pub async fn upsert(
+        db: &PgPool,
+        org: &str,
+        repo: &str,
+        info: &RepoInfo,
+    ) -> Result<(), DbError> {
+        sqlx::query(
+            r#"INSERT INTO repo_info (org, repo, description)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (org, repo) DO UPDATE SET description = excluded.description"#,
+        )
+        .bind(org)
+        .bind(repo)
+        .bind(&info.description)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the description of every repo that has one, as `(org, repo, description)`
+    pub async fn fetch_descriptions(db: &PgPool) -> Result<Vec<(String, String, String)>, DbError> {
+        let resp: Vec<(String, String, String)> =
+            sqlx::query_as("SELECT org, repo, description FROM repo_info")
+                .fetch_all(db)
+                .await?;
+
+        Ok(resp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::capture_logs;
+
+    #[test]
+    fn zero_secs_means_never() {
+        assert_eq!(secs_or_never(0), None);
+        assert_eq!(secs_or_never(90), Some(Duration::from_secs(90)));
+    }
+
+    #[sqlx::test]
+    async fn recycles_connections(_: PgPoolOptions, connect_options: PgConnectOptions) {
+        let lifetime = Duration::from_millis(200);
+        let db = pool_options(1, Some(lifetime), Some(Duration::from_secs(60)))
+            .connect_with(connect_options)
+            .await
+            .unwrap();
+        assert_eq!(db.options().get_max_lifetime(), Some(lifetime));
+        assert_eq!(
+            db.options().get_idle_timeout(),
+            Some(Duration::from_secs(60))
+        );
+
+        let backend = || async {
+            let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+            pid
+        };
+
+        // The single connection is reused until it's outlived its lifetime
+        let first = backend().await;
+        assert_eq!(backend().await, first);
+        tokio::time::sleep(lifetime * 2).await;
+        assert_ne!(backend().await, first);
+
+        let never = pool_options(1, None, None);
+        assert_eq!(never.get_max_lifetime(), None);
+        assert_eq!(never.get_idle_timeout(), None);
+    }
+
+    #[test]
+    fn caps_the_statement_timeout_to_the_request_deadline() {
+        let second = Some(Duration::from_secs(1));
+        assert_eq!(capped_statement_timeout(30_000, None), 30_000);
+        assert_eq!(capped_statement_timeout(0, None), 0);
+        assert_eq!(capped_statement_timeout(30_000, second), 1_100);
+        assert_eq!(capped_statement_timeout(0, second), 1_100);
+        assert_eq!(capped_statement_timeout(500, second), 500);
+    }
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(quote_identifier("public"), "\"public\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+
+    // Time is paused, so the backoff sleeps return as soon as nothing else is left to run
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_success() {
+        let start = tokio::time::Instant::now();
+        let mut attempts = 0;
+        let resp: Result<u32, String> = retry_with_backoff(5, || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 4 {
+                    Err(format!("attempt {attempt} failed"))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(resp, Ok(4));
+        // Three retries, each waiting twice as long as the one before
+        assert_eq!(start.elapsed(), INITIAL_RETRY_DELAY * 7);
+    }
+
+    // Assumes `SLOW_QUERY_MS` isn't set, leaving the default of 500
+    #[sqlx::test]
+    async fn warns_about_slow_queries(db: PgPool) {
+        let (logs, _guard) = capture_logs();
+
+        log_if_slow("quick", sqlx::query("SELECT 1").execute(&db))
+            .await
+            .unwrap();
+        log_if_slow("sleepy", sqlx::query("SELECT pg_sleep(0.6)").execute(&db))
+            .await
+            .unwrap();
+
+        let logs = logs.contents();
+        assert!(logs.contains("WARN"), "{logs}");
+        assert!(logs.contains("Slow query sleepy took"), "{logs}");
+        assert!(!logs.contains("quick"), "{logs}");
+    }
+
+    #[test]
+    fn escapes_startup_options() {
+        assert_eq!(escape_option("public"), "public");
+        assert_eq!(escape_option(r#""Team A""#), r#""Team\ A""#);
+        assert_eq!(escape_option("a\\b\tc"), "a\\\\b\\\tc");
+    }
+
+    #[sqlx::test]
+    async fn sets_up_tables_in_the_configured_schema(
+        pool_options: PgPoolOptions,
+        connect_options: PgConnectOptions,
+    ) {
+        let schema = "Team \"A\"";
+        let admin = pool_options
+            .clone()
+            .connect_with(connect_options.clone())
+            .await
+            .unwrap();
+        sqlx::query(&format!("CREATE SCHEMA {}", quote_identifier(schema)))
+            .execute(&admin)
+            .await
+            .unwrap();
+
+        let search_path = escape_option(&quote_identifier(schema));
+        let connect_options = connect_options.options([("search_path", search_path)]);
+        let db = pool_options.connect_with(connect_options).await.unwrap();
+        setup_tables(&db).await.unwrap();
+
+        let upload = serde_json::from_value(serde_json::json!({
+            "branch_covered": 1, "branch_total": 2, "branch_percent": 50.0,
+            "function_covered": 3, "function_total": 4, "function_percent": 75.0,
+            "line_covered": 5, "line_total": 10, "line_percent": 50.0,
+        }))
+        .unwrap();
+        summary::insert_into_table(&db, "org", "repo", &upload)
+            .await
+            .unwrap();
+
+        let schemas: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT table_schema::text FROM information_schema.tables WHERE table_name = 'latest_summary'",
+        )
+        .fetch_all(&admin)
+        .await
+        .unwrap();
+        assert_eq!(schemas, [schema]);
+
+        let count: i64 = sqlx::query_scalar(&format!(
+            "SELECT count(*) FROM {}.summary",
+            quote_identifier(schema)
+        ))
+        .fetch_one(&admin)
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[sqlx::test]
+    async fn statement_timeout_is_lifted_for_one_transaction(
+        pool_options: PgPoolOptions,
+        connect_options: PgConnectOptions,
+    ) {
+        let connect_options = connect_options.options([("statement_timeout", "10")]);
+        // A single connection, so every query below runs on the same one
+        let db = pool_options
+            .max_connections(1)
+            .connect_with(connect_options)
+            .await
+            .unwrap();
+        let sleep = || sqlx::query("SELECT pg_sleep(0.2)");
+
+        let err = sleep().execute(&db).await.unwrap_err();
+        let code = err.as_database_error().and_then(|e| e.code());
+        // query_canceled
+        assert_eq!(code.as_deref(), Some("57014"));
+
+        let mut tx = db.begin().await.unwrap();
+        disable_statement_timeout(&mut tx).await.unwrap();
+        sleep().execute(&mut *tx).await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert!(sleep().execute(&db).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn warm_up_leaves_idle_connections(db: PgPool) {
+        warm_up(&db, 3).await.unwrap();
+        assert!(db.size() >= 3, "pool has {} connections", db.size());
+
+        // Dropped connections are handed back to the pool in the background
+        for _ in 0..100 {
+            if db.num_idle() >= 3 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("pool has {} idle connections", db.num_idle());
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_once_retries_run_out() {
+        let resp: Result<(), &str> = retry_with_backoff(0, || async { Err("down") }).await;
+
+        assert_eq!(resp, Err("down"));
+    }
 }
@@ -0,0 +1,282 @@
+use crate::db::summary::CoverageSummary;
+use axum::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+
+const DEFAULT_EVENT_CHANNEL: &str = "gcov.summaries";
+/// How long publishing an event may take before it's abandoned, so an unreachable broker can't
+/// leave publishing tasks piling up
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Published whenever a summary is stored
+#[derive(Serialize)]
+pub struct SummaryEvent {
+    pub org: String,
+    pub repo: String,
+    /// Unix timestamp the summary was stored at
+    pub insert_time: i64,
+    pub coverage: CoverageSummary,
+}
+
+/// Somewhere summary events can be published to, e.g. a message broker
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, event: &SummaryEvent) -> anyhow::Result<()>;
+}
+
+/// The configured publisher, or `None` when publishing is disabled
+pub type Events = Option<Arc<dyn EventPublisher>>;
+
+/// Publishes `event` in the background, giving up after `PUBLISH_TIMEOUT`. Publishing is
+/// best-effort, so a broker outage never fails or slows down an upload and is only logged.
+pub fn spawn_publish(publisher: Arc<dyn EventPublisher>, event: SummaryEvent) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = publish_with_timeout(&*publisher, &event, PUBLISH_TIMEOUT).await {
+            tracing::warn!(
+                "Failed to publish summary event for {}/{}: {}",
+                event.org,
+                event.repo,
+                e
+            );
+        }
+    })
+}
+
+async fn publish_with_timeout(
+    publisher: &dyn EventPublisher,
+    event: &SummaryEvent,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    tokio::time::timeout(timeout, publisher.publish(event))
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {:?}", timeout))?
+}
+
+/// Creates the publisher configured by `$EVENT_BROKER_URL`, exiting the process if it's invalid.
+/// Only Redis brokers are supported, as `redis://[[user]:password@]host[:port]` or `rediss://...`
+/// for TLS, publishing to `$EVENT_CHANNEL`.
+pub fn from_env() -> Events {
+    let url = std::env::var("EVENT_BROKER_URL").ok()?;
+
+    match RedisPublisher::from_url(&url) {
+        Ok(publisher) => Some(Arc::new(publisher)),
+        Err(e) => {
+            eprintln!("Invalid value for EVENT_BROKER_URL: {}", e);
+            ::std::process::exit(2);
+        }
+    }
+}
+
+/// Publishes events with Redis' `PUBLISH`. The connection is opened with the first event, so an
+/// unreachable broker doesn't hold up startup, and is reopened in the background after any error.
+struct RedisPublisher {
+    client: redis::Client,
+    channel: String,
+    conn: OnceCell<ConnectionManager>,
+}
+
+impl RedisPublisher {
+    fn from_url(url: &str) -> anyhow::Result<Self> {
+        // The client also takes unix sockets, which aren't meant for brokers elsewhere
+        let scheme = url::Url::parse(url)?.scheme().to_string();
+        if scheme != "redis" && scheme != "rediss" {
+            anyhow::bail!("unsupported scheme {:?}, expected redis or rediss", scheme);
+        }
+
+        Ok(RedisPublisher {
+            client: redis::Client::open(url)?,
+            channel: std::env::var("EVENT_CHANNEL").unwrap_or(DEFAULT_EVENT_CHANNEL.to_string()),
+            conn: OnceCell::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for RedisPublisher {
+    async fn publish(&self, event: &SummaryEvent) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec(event)?;
+
+        let mut conn = self
+            .conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?
+            .clone();
+        let _: () = conn.publish(&self.channel, payload).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::summary::Coverage;
+    use std::collections::BTreeMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    /// Records every event it's asked to publish, or never finishes when `hang` is set
+    #[derive(Default)]
+    struct FakePublisher {
+        published: std::sync::Mutex<Vec<String>>,
+        hang: bool,
+    }
+
+    #[async_trait]
+    impl EventPublisher for FakePublisher {
+        async fn publish(&self, event: &SummaryEvent) -> anyhow::Result<()> {
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+            self.published
+                .lock()
+                .unwrap()
+                .push(serde_json::to_string(event)?);
+            Ok(())
+        }
+    }
+
+    fn event() -> SummaryEvent {
+        SummaryEvent {
+            org: "org".to_string(),
+            repo: "repo".to_string(),
+            insert_time: 1700000000,
+            coverage: CoverageSummary {
+                branch: Coverage::from_counts(0, 0),
+                function: Coverage::from_counts(1, 2),
+                line: Coverage::from_counts(3, 4),
+                extra: BTreeMap::new(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn publishes_in_background() {
+        let publisher = Arc::new(FakePublisher::default());
+
+        spawn_publish(publisher.clone(), event()).await.unwrap();
+
+        let published = publisher.published.lock().unwrap();
+        assert_eq!(published.len(), 1);
+        assert!(published[0].contains(r#""org":"org","repo":"repo""#));
+        assert!(published[0].contains(r#""line_covered":3"#));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_a_hanging_broker() {
+        let publisher = FakePublisher {
+            hang: true,
+            ..Default::default()
+        };
+
+        let resp = publish_with_timeout(&publisher, &event(), Duration::from_millis(10)).await;
+
+        assert!(resp.unwrap_err().to_string().contains("timed out"));
+        assert!(publisher.published.lock().unwrap().is_empty());
+    }
+
+    /// A Redis server accepting a single connection, which records the commands sent over it and
+    /// replies to `PUBLISH` with `publish_reply` and to anything else with `+OK`
+    struct FakeRedis {
+        url: String,
+        commands: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl FakeRedis {
+        async fn start(credentials: &str, publish_reply: &'static str) -> FakeRedis {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("redis://{credentials}{}", listener.local_addr().unwrap());
+            let commands = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+            let recorded = commands.clone();
+            tokio::spawn(async move {
+                let (conn, _) = listener.accept().await.unwrap();
+                let mut conn = tokio::io::BufReader::new(conn);
+
+                while let Some(command) = read_command(&mut conn).await {
+                    let reply = match command[0].as_str() {
+                        "PUBLISH" => publish_reply,
+                        _ => "+OK\r\n",
+                    };
+                    recorded.lock().unwrap().push(command);
+                    conn.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                }
+            });
+
+            FakeRedis { url, commands }
+        }
+
+        fn commands(&self, name: &str) -> Vec<Vec<String>> {
+            let commands = self.commands.lock().unwrap();
+            commands.iter().filter(|c| c[0] == name).cloned().collect()
+        }
+    }
+
+    /// Reads a command sent as an array of bulk strings, or `None` once the client disconnects
+    async fn read_command(
+        conn: &mut tokio::io::BufReader<tokio::net::TcpStream>,
+    ) -> Option<Vec<String>> {
+        async fn read_header(
+            conn: &mut tokio::io::BufReader<tokio::net::TcpStream>,
+            prefix: char,
+        ) -> Option<usize> {
+            let mut line = String::new();
+            conn.read_line(&mut line).await.ok()?;
+            line.strip_prefix(prefix)?.trim_end().parse().ok()
+        }
+
+        let len = read_header(conn, '*').await?;
+        let mut args = Vec::with_capacity(len);
+        for _ in 0..len {
+            let arg_len = read_header(conn, '$').await?;
+            let mut arg = vec![0; arg_len + 2];
+            conn.read_exact(&mut arg).await.ok()?;
+            arg.truncate(arg_len);
+            args.push(String::from_utf8(arg).ok()?);
+        }
+
+        Some(args)
+    }
+
+    #[tokio::test]
+    async fn publishes_to_redis() {
+        let redis = FakeRedis::start("gcov:secret@", ":1\r\n").await;
+        let publisher = RedisPublisher::from_url(&redis.url).unwrap();
+
+        publisher.publish(&event()).await.unwrap();
+        publisher.publish(&event()).await.unwrap();
+
+        // The connection is authenticated with the ACL user, once
+        assert_eq!(redis.commands("AUTH"), [["AUTH", "gcov", "secret"]]);
+
+        let published = redis.commands("PUBLISH");
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0][1], DEFAULT_EVENT_CHANNEL);
+        let payload: serde_json::Value = serde_json::from_str(&published[0][2]).unwrap();
+        assert_eq!(payload["org"], "org");
+        assert_eq!(payload["coverage"]["line_covered"], 3);
+    }
+
+    #[tokio::test]
+    async fn reports_redis_errors() {
+        let redis = FakeRedis::start("", "-ERR no publishing today\r\n").await;
+        let publisher = RedisPublisher::from_url(&redis.url).unwrap();
+
+        let err = publisher.publish(&event()).await.unwrap_err();
+
+        assert!(err.to_string().contains("no publishing today"), "{err}");
+        assert!(redis.commands("AUTH").is_empty());
+    }
+
+    #[test]
+    fn accepts_only_redis_urls() {
+        assert!(RedisPublisher::from_url("nats://localhost").is_err());
+        assert!(RedisPublisher::from_url("unix:///tmp/redis.sock").is_err());
+        assert!(RedisPublisher::from_url("redis://:pw@localhost:6380").is_ok());
+        assert!(RedisPublisher::from_url("rediss://user:pw@redis.example.com").is_ok());
+    }
+}
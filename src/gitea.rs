@@ -0,0 +1,211 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Context commit statuses are posted under, grouping them in Gitea's status list
+const STATUS_CONTEXT: &str = "coverage";
+/// Longest response body worth reading back to explain a rejected status
+const MAX_ERROR_BODY_BYTES: usize = 1024;
+/// How long to wait for Gitea to accept a status before giving up on it
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for a connection to Gitea to be established
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default for `$GITEA_MAX_PENDING`
+const DEFAULT_MAX_PENDING: usize = 16;
+
+/// Whether a commit's coverage met its repo's thresholds
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusState {
+    Success,
+    Failure,
+}
+
+/// A commit status as accepted by Gitea's `POST /repos/{owner}/{repo}/statuses/{sha}`
+#[derive(Serialize, Debug)]
+pub struct CommitStatus {
+    pub state: StatusState,
+    pub description: String,
+    pub context: &'static str,
+}
+
+impl CommitStatus {
+    pub fn new(state: StatusState, description: String) -> Self {
+        CommitStatus {
+            state,
+            description,
+            context: STATUS_CONTEXT,
+        }
+    }
+}
+
+/// Posts commit statuses to a Gitea instance, over HTTPS unless plain HTTP is explicitly allowed.
+/// At most `$GITEA_MAX_PENDING` statuses are in flight at once so a slow instance can't pile up
+/// background tasks.
+pub struct GiteaClient {
+    http: reqwest::Client,
+    base: url::Url,
+    token: String,
+    pending: Arc<Semaphore>,
+}
+
+/// The configured client, or `None` when commit statuses aren't posted
+pub type Gitea = Option<Arc<GiteaClient>>;
+
+/// Creates the client configured by `$GITEA_URL` and `$GITEA_TOKEN`, or `None` when `$GITEA_URL`
+/// isn't set, exiting the process if either is invalid. `http://` URLs are refused, as the token
+/// would be sent in cleartext, unless `$GITEA_ALLOW_INSECURE` is `true`.
+pub fn from_env() -> Option<GiteaClient> {
+    let url = std::env::var("GITEA_URL").ok()?;
+
+    let Ok(token) = std::env::var("GITEA_TOKEN") else {
+        eprintln!("GITEA_TOKEN must be set alongside GITEA_URL");
+        ::std::process::exit(2);
+    };
+
+    let allow_insecure = crate::parse_env_var_or("GITEA_ALLOW_INSECURE", false);
+    let max_pending = crate::parse_env_var_or("GITEA_MAX_PENDING", DEFAULT_MAX_PENDING).max(1);
+
+    match GiteaClient::new(&url, token, allow_insecure, max_pending) {
+        Ok(client) => Some(client),
+        Err(e) => {
+            eprintln!("Invalid value for GITEA_URL: {}", e);
+            ::std::process::exit(2);
+        }
+    }
+}
+
+impl GiteaClient {
+    /// Creates a client posting to the Gitea instance at `url` with `token`, refusing `http://`
+    /// URLs unless `allow_insecure` is set
+    pub fn new(
+        url: &str,
+        token: String,
+        allow_insecure: bool,
+        max_pending: usize,
+    ) -> anyhow::Result<Self> {
+        let base = url::Url::parse(url)?;
+
+        match base.scheme() {
+            "https" => {}
+            "http" if allow_insecure => {}
+            "http" => anyhow::bail!(
+                "refusing to send GITEA_TOKEN over plain http, set GITEA_ALLOW_INSECURE=true to allow it"
+            ),
+            scheme => anyhow::bail!("unsupported scheme {:?}, expected https", scheme),
+        }
+        if base.host_str().is_none() {
+            anyhow::bail!("missing host");
+        }
+        if base.cannot_be_a_base() {
+            anyhow::bail!("GITEA_URL can't have a path appended");
+        }
+
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(STATUS_TIMEOUT)
+            .build()?;
+
+        Ok(GiteaClient {
+            http,
+            base,
+            token,
+            pending: Arc::new(Semaphore::new(max_pending)),
+        })
+    }
+
+    /// Reserves one of the `$GITEA_MAX_PENDING` slots for posting a status, or `None` if they're
+    /// all taken and the status should be dropped
+    pub fn try_reserve(&self) -> Option<OwnedSemaphorePermit> {
+        self.pending.clone().try_acquire_owned().ok()
+    }
+
+    /// Sets the status of `commit` in `org`/`repo`, failing if Gitea doesn't accept it within
+    /// `STATUS_TIMEOUT`
+    pub async fn post_status(
+        &self,
+        org: &str,
+        repo: &str,
+        commit: &str,
+        status: &CommitStatus,
+    ) -> anyhow::Result<()> {
+        match tokio::time::timeout(STATUS_TIMEOUT, self.send_status(org, repo, commit, status))
+            .await
+        {
+            Ok(result) => result,
+            Err(_) => anyhow::bail!("Gitea didn't reply within {:?}", STATUS_TIMEOUT),
+        }
+    }
+
+    async fn send_status(
+        &self,
+        org: &str,
+        repo: &str,
+        commit: &str,
+        status: &CommitStatus,
+    ) -> anyhow::Result<()> {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("GITEA_URL can't have a path appended"))?
+            .pop_if_empty()
+            .extend(["api", "v1", "repos", org, repo, "statuses", commit]);
+
+        let response = self
+            .http
+            .post(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("token {}", self.token),
+            )
+            .json(status)
+            .send()
+            .await?;
+
+        let code = response.status();
+        if !code.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let body = crate::truncate_on_char_boundary(body.trim(), MAX_ERROR_BODY_BYTES);
+            anyhow::bail!("Gitea replied with {}: {}", code, body);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(url: &str, allow_insecure: bool) -> anyhow::Result<GiteaClient> {
+        GiteaClient::new(url, "token".to_string(), allow_insecure, 1)
+    }
+
+    #[test]
+    fn accepts_https() {
+        assert!(client("https://gitea.example.com", false).is_ok());
+    }
+
+    #[test]
+    fn refuses_plain_http_unless_allowed() {
+        assert!(client("http://gitea.example.com", false).is_err());
+        assert!(client("http://gitea.example.com", true).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_schemes() {
+        assert!(client("ftp://gitea.example.com", true).is_err());
+        assert!(client("mailto:gitea@example.com", true).is_err());
+    }
+
+    #[test]
+    fn bounds_pending_statuses() {
+        let client = client("https://gitea.example.com", false).unwrap();
+        let permit = client.try_reserve();
+        assert!(permit.is_some());
+        assert!(client.try_reserve().is_none());
+        drop(permit);
+        assert!(client.try_reserve().is_some());
+    }
+}
@@ -1,34 +1,218 @@
 use axum::{
-    extract::{Json, Path},
-    http::StatusCode,
+    async_trait,
+    body::Bytes,
+    extract::{
+        rejection::{ExtensionRejection, JsonRejection},
+        ConnectInfo, Form, FromRequest, FromRequestParts, Json, OriginalUri, Path, Query, State,
+    },
+    http::{header, request::Parts, HeaderMap, Method, Request, StatusCode},
+    middleware::{self, Next},
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, post, put, MethodRouter},
     Extension, Router,
 };
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
 use lazy_static::lazy_static;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
 use sqlx::postgres::PgPool;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tera::Tera;
-use tower_http::trace::TraceLayer;
-use tracing;
-use tracing_appender;
+use tokio::sync::Mutex;
+use tower_http::{decompression::RequestDecompressionLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod batch;
+mod chart;
 pub mod db;
-use db::summary::{CoverageSummary, SummaryTableEntry};
+mod events;
+mod gitea;
+mod metrics;
+mod stats;
+#[cfg(test)]
+mod test_util;
+mod velocity;
+use batch::Batcher;
+use db::annotations::{Annotation, NewAnnotation};
+use db::failures::FailedUpload;
+use db::goals::CoverageGoals;
+use db::quota::{OrgQuota, QuotaExceeded};
+use db::repo_config::RepoConfig;
+use db::repo_info::RepoInfo;
+use db::summary::{
+    Bucket, Coverage, CoverageSummary, FlatSummary, Inserted, SortDir, SortField,
+    SummaryTableEntry, SummaryUpload, TableOptions, ToolInfo,
+};
+use events::{Events, SummaryEvent};
+use gitea::{CommitStatus, Gitea, GiteaClient, StatusState};
 
 const MAX_LOG_FILES: usize = 48;
+const REPORTS_DIR: &str = "reports";
+const STATS_CACHE_TTL: Duration = Duration::from_secs(10);
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_RAW_SUMMARIES: i64 = 10000;
+const MAX_LISTED_FAILURES: i64 = 100;
+/// Bytes of a failed upload kept to help diagnose it
+const MAX_FAILURE_PAYLOAD_BYTES: usize = 4096;
+/// Header counting the rows left out of a listing because their stored coverage couldn't be parsed
+const SKIPPED_ROWS_HEADER: &str = "x-skipped-rows";
+/// Rows buffered between the DB and the client when streaming an export
+const EXPORT_BUFFER_ROWS: usize = 64;
+const MAX_IDENTIFIER_LEN: usize = 100;
+const MAX_DESCRIPTION_LEN: usize = 500;
+const MAX_ANNOTATION_LEN: usize = 2000;
+/// Longest commit status description sent to Gitea, which shows it inline in the commit status list
+const MAX_STATUS_DESCRIPTION_LEN: usize = 255;
+const TREND_CHART_WIDTH: f64 = 600.0;
+const TREND_CHART_HEIGHT: f64 = 200.0;
+/// Blank space left around the PNG trend chart so lines at 0% or 100% aren't clipped
+const TREND_PNG_PADDING: f64 = 10.0;
+/// How long clients may cache a rendered PNG trend chart
+const TREND_PNG_MAX_AGE_SECS: u64 = 300;
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>`, required on uploads to repos with a secret
+const SIGNATURE_HEADER: &str = "x-coverage-signature";
+/// Shown in place of a page whose template failed to render
+const RENDER_ERROR_PAGE: &str = "<!DOCTYPE html>\n<html>\n<head><title>Error</title></head>\n<body>\n<h1>Something went wrong</h1>\n<p>This page couldn't be displayed. Please try again later.</p>\n</body>\n</html>\n";
+const DEFAULT_LISTEN_BACKLOG: i32 = 1024;
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
 
-struct AppError(anyhow::Error);
+#[derive(Debug)]
+enum AppError {
+    /// The uploaded coverage failed validation
+    InvalidCoverage(Vec<String>),
+    /// The uploaded coverage has no cases at all and `ALLOW_EMPTY_COVERAGE` isn't set
+    EmptyCoverage,
+    /// The request lacked a valid admin token
+    Unauthorized,
+    /// The upload's `X-Coverage-Signature` is missing or doesn't match its repo's secret
+    InvalidSignature,
+    /// The request isn't allowed, e.g. the org isn't on the allowlist
+    Forbidden(String),
+    /// The requested resource doesn't exist
+    NotFound(String),
+    /// The request is malformed, e.g. an invalid org name
+    BadRequest(String),
+    /// The request clashes with existing data, e.g. moving a repo onto one that already has coverage
+    Conflict(String),
+    /// The client has sent too much too quickly, e.g. exceeding its org's upload quota
+    TooManyRequests(String),
+    /// The request body isn't JSON
+    UnsupportedMediaType,
+    /// The request would modify something while `READ_ONLY` is set
+    ReadOnly,
+    /// The request was abandoned after running longer than `REQUEST_TIMEOUT_MS`
+    Timeout,
+    /// A page template failed to render. The detail is only logged, as it can expose template
+    /// internals.
+    Render(tera::Error),
+    /// Anything else, reported as an internal error
+    Other(anyhow::Error),
+}
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", self.0),
+        match self {
+            AppError::InvalidCoverage(errors) => (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "valid": false, "errors": errors })),
+            )
+                .into_response(),
+            AppError::EmptyCoverage => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Coverage has a total of 0 for every metric, did the test run fail?",
+            )
+                .into_response(),
+            AppError::Unauthorized => {
+                (StatusCode::UNAUTHORIZED, "Missing or invalid admin token").into_response()
+            }
+            AppError::InvalidSignature => (
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid X-Coverage-Signature header",
+            )
+                .into_response(),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg).into_response(),
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg).into_response(),
+            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg).into_response(),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg).into_response(),
+            AppError::ReadOnly => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The server is in read-only mode for maintenance, please try again later",
+            )
+                .into_response(),
+            AppError::Timeout => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The request took too long and was abandoned, please try again later",
+            )
+                .into_response(),
+            AppError::UnsupportedMediaType => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Coverage must be uploaded as JSON, with a `Content-Type: application/json` header",
+            )
+                .into_response(),
+            AppError::Render(e) => {
+                // Tera keeps the useful part, e.g. the failing variable, in the error's sources
+                let mut detail = e.to_string();
+                let mut source = std::error::Error::source(&e);
+                while let Some(cause) = source {
+                    detail += &format!(": {}", cause);
+                    source = cause.source();
+                }
+                tracing::error!("Failed to render page: {}", detail);
+
+                (StatusCode::INTERNAL_SERVER_ERROR, Html(RENDER_ERROR_PAGE)).into_response()
+            }
+            AppError::Other(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Something went wrong: {}", e),
+            )
+                .into_response(),
+        }
+    }
+}
+
+impl AppError {
+    /// Whether an upload failing with this error is worth recording in `failed_uploads`, i.e. the
+    /// coverage itself was bad or couldn't be stored. Uploads turned away by policy, like the org
+    /// allowlist, quotas, `STRICT_REPOS` or a bad signature, aren't recorded, as anyone can send
+    /// those and they'd fill the table.
+    fn is_upload_failure(&self) -> bool {
+        matches!(
+            self,
+            AppError::InvalidCoverage(_)
+                | AppError::EmptyCoverage
+                | AppError::BadRequest(_)
+                | AppError::Other(_)
         )
-            .into_response()
+    }
+
+    /// Describes the error in a single line, e.g. for recording it
+    fn describe(&self) -> String {
+        match self {
+            AppError::InvalidCoverage(errors) => format!("Invalid coverage: {}", errors.join("; ")),
+            AppError::EmptyCoverage => "Coverage has a total of 0 for every metric".to_string(),
+            AppError::Unauthorized => "Missing or invalid admin token".to_string(),
+            AppError::InvalidSignature => {
+                "Missing or invalid X-Coverage-Signature header".to_string()
+            }
+            AppError::Forbidden(msg)
+            | AppError::NotFound(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Conflict(msg)
+            | AppError::TooManyRequests(msg) => msg.clone(),
+            AppError::UnsupportedMediaType => "Coverage wasn't uploaded as JSON".to_string(),
+            AppError::ReadOnly => "The server is in read-only mode".to_string(),
+            AppError::Timeout => "The request timed out".to_string(),
+            AppError::Render(e) => e.to_string(),
+            AppError::Other(e) => e.to_string(),
+        }
     }
 }
 
@@ -37,29 +221,542 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self(err.into())
+        Self::Other(err.into())
+    }
+}
+
+/// Query parameters accepted by the JSON summary endpoint
+#[derive(Deserialize)]
+struct SummaryQuery {
+    /// Only consider summaries tagged with this label
+    label: Option<String>,
+    /// Field to sort by, defaulting to org then repo
+    sort: Option<SortField>,
+    /// Direction to sort in
+    #[serde(default)]
+    dir: SortDir,
+    /// Flatten each summary's coverage down to the percentage of each metric
+    #[serde(default)]
+    flat: bool,
+    /// Only include repos that have uploaded at least this many summaries, hiding one-off misfires
+    min_uploads: Option<i64>,
+}
+
+/// Query parameters accepted by the coverage check endpoint, overriding the repo's stored thresholds
+#[derive(Deserialize)]
+struct CheckQuery {
+    line: Option<f64>,
+    branch: Option<f64>,
+    function: Option<f64>,
+}
+
+/// Query parameters accepted by the coverage velocity endpoint
+#[derive(Deserialize)]
+struct VelocityQuery {
+    #[serde(default)]
+    window: velocity::Window,
+}
+
+/// Body of a request to compare two summaries
+#[derive(Deserialize)]
+struct CompareRequest {
+    baseline: CoverageSummary,
+    current: CoverageSummary,
+}
+
+/// Query parameters accepted by the trend page
+#[derive(Deserialize)]
+struct TrendQuery {
+    /// Downsample the history to the last summary in each period, rather than charting every one
+    bucket: Option<Bucket>,
+}
+
+/// Query parameters naming the baseline to mark
+#[derive(Deserialize)]
+struct BaselineQuery {
+    name: String,
+}
+
+/// Query parameters naming the baseline to compare against
+#[derive(Deserialize)]
+struct DeltaQuery {
+    baseline: String,
+}
+
+/// A summary upload sent as form fields, e.g. `line_covered=5&line_total=6&...`, for CI scripts that
+/// can't easily build JSON. Percentages are always computed from the counts, so they aren't needed.
+#[derive(Deserialize)]
+struct FormUpload {
+    line_covered: usize,
+    line_total: usize,
+    branch_covered: usize,
+    branch_total: usize,
+    function_covered: usize,
+    function_total: usize,
+    format_version: Option<String>,
+    gcc_version: Option<String>,
+    commit: Option<String>,
+    pr_number: Option<i32>,
+    build_url: Option<String>,
+    branch: Option<String>,
+}
+
+impl From<FormUpload> for SummaryUpload {
+    fn from(form: FormUpload) -> Self {
+        SummaryUpload {
+            coverage: CoverageSummary {
+                branch: Coverage::from_counts(form.branch_covered, form.branch_total),
+                function: Coverage::from_counts(form.function_covered, form.function_total),
+                line: Coverage::from_counts(form.line_covered, form.line_total),
+                extra: BTreeMap::new(),
+            },
+            tool: ToolInfo {
+                format_version: form.format_version,
+                gcc_version: form.gcc_version,
+            },
+            labels: Vec::new(),
+            commit: form.commit,
+            pr_number: form.pr_number,
+            build_url: form.build_url,
+            branch: form.branch,
+            insert_time: None,
+        }
+    }
+}
+
+/// Query parameters accepted by paginated endpoints
+#[derive(Deserialize)]
+struct PageQuery {
+    /// Cursor returned as `next_cursor` by the previous page
+    after: Option<String>,
+}
+
+/// Body of a request to move a repo's data to a new org/repo
+#[derive(Deserialize)]
+struct MoveRequest {
+    from_org: String,
+    from_repo: String,
+    to_org: String,
+    to_repo: String,
+}
+
+/// Body of a request to set a repo's upload signing secret
+#[derive(Deserialize)]
+struct SecretRequest {
+    secret: String,
+}
+
+/// Extractor guarding the admin endpoints, requiring an `Authorization: Bearer <ADMIN_TOKEN>` header.
+/// All admin requests are rejected when `ADMIN_TOKEN` isn't set.
+struct AdminAuth;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminAuth
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let provided = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+
+        match (ADMIN_TOKEN.as_deref(), provided) {
+            (Some(expected), Some(provided)) if expected == provided => Ok(AdminAuth),
+            _ => Err(AppError::Unauthorized),
+        }
+    }
+}
+
+/// Extractor for a JSON upload body, like `Json` but with a clearer error when the client didn't send
+/// a JSON content type, which is an easy mistake to make with curl in CI
+struct JsonUpload<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonUpload<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(
+        req: Request<axum::body::Body>,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(JsonUpload(value)),
+            Err(JsonRejection::MissingJsonContentType(_)) => {
+                Err(AppError::UnsupportedMediaType.into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
+
+/// Wraps another body extractor, also keeping the raw body and its `X-Coverage-Signature` header so
+/// the signature can be verified with `verify_signature`. A body the wrapped extractor rejects, e.g.
+/// a summary missing a count, is recorded as a failed upload to the path's org and repo.
+struct Signed<E> {
+    inner: E,
+    body: Bytes,
+    signature: Option<String>,
+}
+
+#[async_trait]
+impl<E, S> FromRequest<S> for Signed<E>
+where
+    E: FromRequest<S>,
+    E::Rejection: IntoResponse,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(
+        req: Request<axum::body::Body>,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let signature = parts
+            .headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let path = Path::<HashMap<String, String>>::from_request_parts(&mut parts, state)
+            .await
+            .ok();
+        let db = parts.extensions.get::<PgPool>().cloned();
+
+        let body = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        // The wrapped extractor gets the same request line and headers alongside the body
+        let req = Request::from_parts(parts, axum::body::Body::from(body.clone()));
+        let rejection = match E::from_request(req, state).await {
+            Ok(inner) => {
+                return Ok(Signed {
+                    inner,
+                    body,
+                    signature,
+                })
+            }
+            Err(rejection) => rejection.into_response(),
+        };
+
+        Err(match (db, path) {
+            (Some(db), Some(path)) => record_rejected_upload(&db, &path, &body, rejection).await,
+            _ => rejection,
+        })
+    }
+}
+
+/// Records a request body that was rejected before it could be parsed, with the rejection's
+/// message as the error, and passes the rejection on. Only client errors are recorded.
+async fn record_rejected_upload(
+    db: &PgPool,
+    path: &HashMap<String, String>,
+    body: &Bytes,
+    rejection: Response,
+) -> Response {
+    let (Some(org), Some(repo)) = (path.get("org"), path.get("repo")) else {
+        return rejection;
+    };
+    if !rejection.status().is_client_error() {
+        return rejection;
+    }
+
+    let (parts, message) = rejection.into_parts();
+    let message = axum::body::to_bytes(message, usize::MAX)
+        .await
+        .unwrap_or_default();
+    let error = format!("Rejected upload: {}", String::from_utf8_lossy(&message));
+    record_failed_upload(db, org, repo, &error, &String::from_utf8_lossy(body)).await;
+
+    Response::from_parts(parts, axum::body::Body::from(message))
+}
+
+/// Acknowledges a stored summary upload
+#[derive(Serialize)]
+struct SummaryAck {
+    org: String,
+    repo: String,
+    /// Percentage of all cases covered across every metric
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    overall_percent: f64,
+    /// How line coverage changed since the repo's previous summary, or null for its first
+    change: Option<CoverageChange>,
+    /// Change in line coverage since the repo's previous latest summary, in percentage points, or
+    /// null for its first
+    line_delta: Option<f64>,
+    /// Whether a summary already stored for the upload's commit was replaced
+    replaced: bool,
+    /// Unix timestamp the summary was stored at
+    insert_time: i64,
+}
+
+/// How a repo's coverage changed, e.g. with an upload or over a window of time
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum CoverageChange {
+    Improved,
+    Regressed,
+    Held,
+}
+
+impl CoverageChange {
+    /// Classifies a change in percentage points, treating anything within `tolerance` of 0 as
+    /// noise. `tolerance` is normally `REGRESSION_TOLERANCE`.
+    fn of(delta: f64, tolerance: f64) -> Self {
+        if delta > tolerance {
+            CoverageChange::Improved
+        } else if delta < -tolerance {
+            CoverageChange::Regressed
+        } else {
+            CoverageChange::Held
+        }
     }
 }
 
+/// A repo's latest coverage within an org matrix, with the change of each metric since the summary
+/// before it (if there was one)
+#[derive(Serialize)]
+struct MatrixRow {
+    repo: String,
+    /// Unix timestamp the latest summary was stored at
+    insert_time: i64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    overall_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    line_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    branch_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    function_percent: f64,
+    line_delta: Option<f64>,
+    branch_delta: Option<f64>,
+    function_delta: Option<f64>,
+}
+
+/// The combined latest coverage of every repo in a team's orgs
+#[derive(Serialize)]
+struct TeamSummary {
+    team: String,
+    orgs: Vec<String>,
+    /// Number of repos the coverage was combined from
+    repos: usize,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    overall_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    line_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    branch_percent: f64,
+    #[serde(serialize_with = "db::summary::serialize_percent")]
+    function_percent: f64,
+}
+
+/// The most recently fetched stats and when they were fetched
+type StatsCache = Arc<Mutex<Option<(Instant, db::Stats)>>>;
+
+/// The most recently fetched latest summary of each repo and when it was fetched. A repo's entry is
+/// dropped whenever a summary is stored for it.
+type LatestCache = Arc<Mutex<HashMap<(String, String), (Instant, FlatSummary)>>>;
+
 #[derive(Serialize, Debug)]
 struct GiteaOrg {
     name: String,
     repos: Vec<SummaryTableEntry>,
+    /// Descriptions of the org's repos that have one, by repo name
+    descriptions: HashMap<String, String>,
 }
 
 lazy_static! {
+    static ref ADMIN_TOKEN: Option<String> = std::env::var("ADMIN_TOKEN").ok();
+    /// Only accept summaries for repos that are already known, rather than creating them
+    static ref STRICT_REPOS: bool = parse_env_var_or("STRICT_REPOS", false);
+    /// Take the client address from `X-Forwarded-For`, which is only trustworthy behind a proxy
+    static ref TRUST_PROXY: bool = parse_env_var_or("TRUST_PROXY", false);
+    /// Host every other hostname is redirected to, e.g. `coverage.example.com`
+    static ref CANONICAL_HOST: Option<String> = std::env::var("CANONICAL_HOST").ok();
+    /// Reject every request that would modify something, e.g. during a DB migration
+    static ref READ_ONLY: bool = parse_env_var_or("READ_ONLY", false);
+    /// How long a request may run before it's abandoned, or `None` to let requests run for as
+    /// long as they need. The DB's statement timeout is capped to it too, see `db::connect_and_setup`.
+    pub(crate) static ref REQUEST_TIMEOUT: Option<Duration> = {
+        let millis = parse_env_var_or("REQUEST_TIMEOUT_MS", 0);
+        (millis > 0).then(|| Duration::from_millis(millis))
+    };
+    /// How long the latest summary of a repo is cached for, as badges and widgets poll it often
+    static ref LATEST_CACHE_TTL: Duration =
+        Duration::from_secs(parse_env_var_or("LATEST_CACHE_SECS", 15));
+    /// Percentage points coverage may wobble by between runs without counting as a change, e.g.
+    /// a regression
+    static ref REGRESSION_TOLERANCE: f64 = parse_env_var_or("REGRESSION_TOLERANCE", 0.1);
+    static ref ALLOW_EMPTY_COVERAGE: bool = parse_env_var_or("ALLOW_EMPTY_COVERAGE", false);
+    /// Orgs allowed to upload coverage, or `None` to allow every org
+    static ref ORG_ALLOWLIST: Option<HashSet<String>> =
+        std::env::var("ORG_ALLOWLIST").ok().map(|v| parse_org_allowlist(&v));
+    /// The most repos rendered on the root page, to keep it responsive on very large instances
+    static ref MAX_RENDERED_REPOS: usize = parse_env_var_or("MAX_RENDERED_REPOS", 1000);
+    /// Teams grouping several orgs together, from `$TEAMS` as a JSON object of team name to orgs
+    static ref TEAMS: HashMap<String, Vec<String>> = match std::env::var("TEAMS") {
+        Ok(v) => match serde_json::from_str(&v) {
+            Ok(teams) => teams,
+            Err(e) => {
+                eprintln!("Invalid value for TEAMS, expected a JSON object of team to orgs: {}", e);
+                ::std::process::exit(2);
+            }
+        },
+        Err(_) => HashMap::new(),
+    };
+    /// Repos left out of the summary listings, from `$HIDDEN_REPOS` as comma-separated `org/repo`s.
+    /// Uploads for them are still accepted.
+    static ref HIDDEN_REPOS: HashSet<(String, String)> = std::env::var("HIDDEN_REPOS")
+        .map(|v| parse_hidden_repos(&v))
+        .unwrap_or_default();
+    static ref SITE_TITLE: Option<String> = std::env::var("SITE_TITLE").ok();
+    static ref SITE_LOGO_URL: Option<String> = std::env::var("SITE_LOGO_URL").ok();
+    static ref SITE_FOOTER: Option<String> = std::env::var("SITE_FOOTER").ok();
     static ref TEMPLATES: Tera = {
-        let tera = match Tera::new("templates/**/*") {
+        let mut tera = match Tera::new("templates/**/*") {
             Ok(t) => t,
             Err(e) => {
                 eprintln!("Parsing error(s): {}", e);
                 ::std::process::exit(1);
             }
         };
+        // Org/repo names and any other values come from clients, so never render them unescaped
+        tera.autoescape_on(vec![".html", ".htm"]);
+        tera.register_filter("markdown", markdown_filter);
         tera
     };
 }
 
+/// Renders one of `TEMPLATES`, keeping any error's detail out of the response
+fn render_page(template: &str, context: &tera::Context) -> Result<Html<String>, AppError> {
+    TEMPLATES
+        .render(template, context)
+        .map(Html)
+        .map_err(AppError::Render)
+}
+
+/// Tera filter rendering a markdown string to HTML, e.g. `{{ description | markdown | safe }}`.
+/// Any raw HTML within the markdown is escaped rather than passed through, and links or images with
+/// a scheme other than `http`, `https` or `mailto`, e.g. `javascript:`, lose their URL.
+fn markdown_filter(
+    value: &tera::Value,
+    _args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    use pulldown_cmark::{CowStr, Event, Tag};
+
+    let markdown = tera::try_get_value!("markdown", "value", String, value);
+
+    let events = pulldown_cmark::Parser::new(&markdown).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if !is_safe_url(&dest_url) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: CowStr::Borrowed(""),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if !is_safe_url(&dest_url) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: CowStr::Borrowed(""),
+            title,
+            id,
+        }),
+        event => event,
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+
+    Ok(tera::Value::String(html))
+}
+
+/// Whether a markdown link may point at `url`: relative URLs and `http`, `https` or `mailto` ones
+fn is_safe_url(url: &str) -> bool {
+    // A scheme ends at the first `:`, which has to come before any path, query or fragment
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = url[..i].to_ascii_lowercase();
+            matches!(scheme.as_str(), "http" | "https" | "mailto")
+        }
+        _ => true,
+    }
+}
+
+/// Fetches and parses the environment variable `key`, falling back to `default` if it isn't set.
+/// Exits the process if the variable is set but can't be parsed.
+fn parse_env_var_or<T>(key: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match std::env::var(key) {
+        Ok(v) => match v.parse() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("${} is invalid: {}", key, e);
+                ::std::process::exit(2);
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Binds a TCP listener to `bind_addr` with the configured listen backlog and keepalive idle time,
+/// both of which are inherited by accepted connections.
+async fn bind_listener(bind_addr: &str) -> std::io::Result<tokio::net::TcpListener> {
+    let backlog = parse_env_var_or("LISTEN_BACKLOG", DEFAULT_LISTEN_BACKLOG);
+    let keepalive_secs = parse_env_var_or("TCP_KEEPALIVE_SECS", DEFAULT_TCP_KEEPALIVE_SECS);
+
+    let addr = tokio::net::lookup_host(bind_addr)
+        .await?
+        .next()
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                "address resolved to nothing",
+            )
+        })?;
+
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    socket.set_reuse_address(true)?;
+    socket.set_tcp_keepalive(
+        &socket2::TcpKeepalive::new().with_time(Duration::from_secs(keepalive_secs)),
+    )?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(backlog)?;
+
+    tracing::info!(
+        "Listening on {} with backlog {} and keepalive {}s",
+        addr,
+        backlog,
+        keepalive_secs
+    );
+
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
 fn configure_logging() -> Result<(), tracing_appender::rolling::InitError> {
     let log_dir = std::env::var("LOG_DIR").unwrap_or("./logs".to_string());
     let log_suffix = std::env::var("LOG_SUFFIX").unwrap_or("log".to_string());
@@ -96,14 +793,19 @@ async fn main() {
         }
     };
 
-    let app = Router::new()
-        .route("/:org/:repo/summary", post(summary_handler))
-        .route("/", get(root_handler))
-        .layer(Extension(db_pool))
-        .layer(TraceLayer::new_for_http());
+    let latest_cache = LatestCache::default();
+    let (batcher, batch_task) = batch::from_env(db_pool.clone(), latest_cache.clone());
+
+    let app = app(
+        db_pool,
+        latest_cache,
+        events::from_env(),
+        batcher,
+        RouterConfig::from_env(),
+    );
 
     let bind_addr = std::env::var("BIND_ADDRESS").unwrap_or("0.0.0.0:1001".to_string());
-    let listener = match tokio::net::TcpListener::bind(&bind_addr).await {
+    let listener = match bind_listener(&bind_addr).await {
         Ok(l) => l,
         Err(e) => {
             eprintln!("Error binding to {}: {}", bind_addr, e);
@@ -111,11 +813,394 @@ async fn main() {
         }
     };
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
+
+    // The router, and with it every `BatchWriter`, is gone now, so the task flushes and finishes
+    if let Some(task) = batch_task {
+        if let Err(e) = task.await {
+            tracing::error!("Batch writer failed while flushing on shutdown: {}", e);
+        }
+    }
+}
+
+/// Settings changing how the router behaves, normally taken from the environment
+#[derive(Clone, Default)]
+struct RouterConfig {
+    /// Turn away every request that would store or change something, see `READ_ONLY`
+    read_only: bool,
+    /// Host to redirect requests for any other host to, see `CANONICAL_HOST`
+    canonical_host: Option<Arc<str>>,
+    /// How long a request may run before it's abandoned, see `REQUEST_TIMEOUT`
+    request_timeout: Option<Duration>,
+    /// Gitea instance commit statuses are posted to once a summary for a commit is stored, see
+    /// `gitea::from_env`
+    gitea: Gitea,
+}
+
+impl RouterConfig {
+    fn from_env() -> Self {
+        RouterConfig {
+            read_only: *READ_ONLY,
+            canonical_host: CANONICAL_HOST.as_deref().map(Arc::from),
+            request_timeout: *REQUEST_TIMEOUT,
+            gitea: gitea::from_env().map(Arc::new),
+        }
+    }
+}
+
+/// Builds the router serving every endpoint, with the shared state its handlers extract
+fn app(
+    db_pool: PgPool,
+    latest_cache: LatestCache,
+    events: Events,
+    batcher: Batcher,
+    config: RouterConfig,
+) -> Router {
+    // Every route that stores or changes something, which is all read-only mode has to turn away.
+    // Validating and comparing summaries are POSTs too, but store nothing, so they're reads. Only
+    // the routes themselves are guarded, so other methods on their paths still get a 405.
+    let write = |route: MethodRouter| {
+        if config.read_only {
+            route.route_layer(middleware::from_fn(read_only_guard))
+        } else {
+            route
+        }
+    };
+    let writes = Router::new()
+        .route("/:org/:repo/summary", write(post(summary_handler)))
+        .route(
+            "/:org/:repo/:commit/summary",
+            write(put(commit_summary_handler)),
+        )
+        .route(
+            "/:org/:repo/summary/merge",
+            write(post(merge_summary_handler)),
+        )
+        .route(
+            "/:org/:repo/summary/form",
+            write(post(form_summary_handler)),
+        )
+        .route(
+            "/:org/:repo/annotations",
+            write(post(add_annotation_handler)),
+        )
+        .route("/:org/:repo/config", write(put(config_handler)))
+        .route("/:org/:repo/info", write(put(info_handler)))
+        .route("/:org/:repo/goals", write(put(set_goals_handler)))
+        .route("/:org/:repo/baseline", write(post(set_baseline_handler)))
+        .route("/admin/move", write(post(admin_move_handler)))
+        .route(
+            "/admin/secrets/:org/:repo",
+            write(put(admin_set_secret_handler).delete(admin_delete_secret_handler)),
+        )
+        .route("/admin/quotas/:org", write(put(admin_set_quota_handler)));
+
+    Router::new()
+        .route("/:org/:repo/summary", get(latest_summary_handler))
+        .route("/:org/:repo/summary.txt", get(plaintext_summary_handler))
+        .route("/:org/:repo/tool", get(tool_info_handler))
+        .route(
+            "/:org/:repo/summary/validate",
+            post(validate_summary_handler),
+        )
+        .route("/:org/:repo/openmetrics", get(openmetrics_handler))
+        .route("/:org/:repo/pushgateway", get(pushgateway_handler))
+        .route("/:org/:repo/metrics", get(available_metrics_handler))
+        .route("/:org/:repo/history.json", get(history_export_handler))
+        .route("/:org/:repo/trend.html", get(trend_page_handler))
+        .route("/:org/:repo/trend.png", get(trend_png_handler))
+        .route("/:org/:repo/commits", get(commits_handler))
+        .route("/:org/:repo/velocity", get(velocity_handler))
+        .route("/:org/:repo/trend/stats", get(trend_stats_handler))
+        .route("/:org/:repo/by-branch", get(by_branch_handler))
+        .route("/:org/:repo/annotations", get(list_annotations_handler))
+        .route("/:org/:repo/pr/:number/summary", get(pr_summary_handler))
+        .route("/:org/:repo/goals", get(goals_handler))
+        .route("/:org/:repo/check", get(check_handler))
+        .route("/:org/:repo/delta", get(delta_handler))
+        .route("/:org/:repo/compare", post(compare_handler))
+        .nest("/api", api_router())
+        .route("/admin/summaries/raw", get(admin_raw_summaries_handler))
+        .route("/admin/export.ndjson", get(admin_export_handler))
+        .route("/admin/failures", get(admin_failures_handler))
+        .route("/admin/quotas/:org", get(admin_quota_handler))
+        .route("/livez", get(livez_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/", get(root_handler))
+        .merge(writes)
+        .route_layer(middleware::from_fn(validate_path_identifiers))
+        .layer(middleware::from_fn_with_state(
+            config.request_timeout,
+            request_deadline,
+        ))
+        .layer(middleware::from_fn_with_state(
+            config.canonical_host,
+            canonical_host_redirect,
+        ))
+        .layer(Extension(db_pool))
+        .layer(Extension(StatsCache::default()))
+        .layer(Extension(latest_cache))
+        .layer(Extension(events))
+        .layer(Extension(batcher))
+        .layer(Extension(config.gitea))
+        // Lets CI upload large coverage reports with `Content-Encoding: gzip`
+        .layer(RequestDecompressionLayer::new())
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &Request<_>| {
+                let client_ip = client_ip(req).map(|ip| ip.to_string());
+                tracing::info_span!(
+                    "request",
+                    method = %req.method(),
+                    uri = %req.uri(),
+                    version = ?req.version(),
+                    client_ip = client_ip.as_deref().unwrap_or("unknown"),
+                )
+            }),
+        )
+}
+
+/// Resolves once the process is asked to stop, with Ctrl-C or `SIGTERM` as sent by `docker stop`
+async fn shutdown_signal() {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Couldn't listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+
+    tracing::info!("Shutting down");
+}
+
+/// Middleware turning away every request it's layered on, i.e. the writes while `READ_ONLY` is set
+async fn read_only_guard(_: Request<axum::body::Body>, _: Next) -> AppError {
+    AppError::ReadOnly
+}
+
+/// Middleware abandoning requests that run past `timeout`, normally `REQUEST_TIMEOUT`. Dropping the handler drops its
+/// in-flight queries, rolling back any transaction, and Postgres' statement timeout, which is capped
+/// to the same deadline, stops the query on the server so its connection is usable again.
+async fn request_deadline(
+    State(timeout): State<Option<Duration>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(timeout) = timeout else {
+        return Ok(next.run(req).await);
+    };
+
+    tokio::time::timeout(timeout, next.run(req))
+        .await
+        .map_err(|_| AppError::Timeout)
+}
+
+/// Checks an org or repo name only uses characters that are safe everywhere it ends up, e.g. as a
+/// directory name, so names like `..` can't escape the reports directory
+fn validate_identifier(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_IDENTIFIER_LEN {
+        return Err(format!(
+            "{name:?} must be between 1 and {MAX_IDENTIFIER_LEN} characters"
+        ));
+    }
+
+    if name == "." || name == ".." {
+        return Err(format!("{name:?} is not a valid name"));
+    }
+
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if !name.chars().all(allowed) {
+        return Err(format!(
+            "{name:?} may only contain letters, digits, '-', '_' and '.'"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Middleware rejecting any request whose `:org` or `:repo` path segment isn't a valid identifier,
+/// before it reaches a handler
+async fn validate_path_identifiers(
+    params: Option<Path<HashMap<String, String>>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    if let Some(Path(params)) = params {
+        for key in ["org", "repo"] {
+            if let Some(value) = params.get(key) {
+                validate_identifier(value).map_err(AppError::BadRequest)?;
+            }
+        }
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Middleware redirecting requests for any host other than `canonical` there, if it's set. Health
+/// checks and metrics scrapes are often addressed by IP or an internal name, so they're never
+/// redirected.
+async fn canonical_host_redirect(
+    State(canonical): State<Option<Arc<str>>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let Some(canonical) = canonical.as_deref() else {
+        return next.run(req).await;
+    };
+
+    let path = req.uri().path();
+    let exempt = matches!(path, "/livez" | "/readyz")
+        || path.ends_with("/openmetrics")
+        || path.ends_with("/pushgateway");
+
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok());
+
+    match host {
+        Some(host) if !exempt && !host.eq_ignore_ascii_case(canonical) => {
+            // We don't terminate TLS ourselves, so only a trusted proxy can tell us the scheme
+            let scheme = req
+                .headers()
+                .get("x-forwarded-proto")
+                .and_then(|p| p.to_str().ok())
+                .filter(|_| *TRUST_PROXY)
+                .unwrap_or("http");
+            let path_and_query = req.uri().path_and_query().map_or("/", |p| p.as_str());
+
+            // A 301 lets clients retry other methods as a GET, dropping the body, which a 308 doesn't
+            let status = if matches!(*req.method(), Method::GET | Method::HEAD) {
+                StatusCode::MOVED_PERMANENTLY
+            } else {
+                StatusCode::PERMANENT_REDIRECT
+            };
+
+            (
+                status,
+                [(
+                    header::LOCATION,
+                    format!("{scheme}://{canonical}{path_and_query}"),
+                )],
+            )
+                .into_response()
+        }
+        _ => next.run(req).await,
+    }
+}
+
+/// The address of the client making `req`, taken from `X-Forwarded-For` when `TRUST_PROXY` is set
+/// and otherwise from the connection
+fn client_ip<B>(req: &Request<B>) -> Option<IpAddr> {
+    if *TRUST_PROXY {
+        if let Some(ip) = forwarded_for(req.headers()) {
+            return Some(ip);
+        }
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+}
+
+/// The client our proxy saw in `X-Forwarded-For`, i.e. the last address it appended. Earlier
+/// addresses come from the client itself, so they could be anything.
+fn forwarded_for(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .next_back()?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Creates a template context containing the site branding, shared by every HTML page.
+/// Unset values are left out so templates can fall back to their defaults.
+fn base_context() -> tera::Context {
+    let mut context = tera::Context::new();
+
+    for (key, value) in [
+        ("site_title", &*SITE_TITLE),
+        ("site_logo_url", &*SITE_LOGO_URL),
+        ("site_footer", &*SITE_FOOTER),
+    ] {
+        if let Some(value) = value {
+            context.insert(key, value);
+        }
+    }
+
+    context
+}
+
+/// Liveness probe, healthy as long as the process is serving requests
+async fn livez_handler() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe, unhealthy while the DB can't be reached
+async fn readyz_handler(db: Extension<PgPool>) -> (StatusCode, &'static str) {
+    match tokio::time::timeout(READINESS_TIMEOUT, db::ping(&db)).await {
+        Ok(Ok(())) => (StatusCode::OK, "ok"),
+        Ok(Err(e)) => {
+            tracing::warn!("Readiness check failed: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, "database unavailable")
+        }
+        Err(_) => {
+            tracing::warn!("Readiness check timed out");
+            (StatusCode::SERVICE_UNAVAILABLE, "database unavailable")
+        }
+    }
+}
+
+/// Routes under `/api`, which respond to unknown paths with a JSON 404
+fn api_router() -> Router {
+    Router::new()
+        .route("/stats", get(api_stats_handler))
+        .route("/summary", get(api_summary_handler))
+        .route("/summary/flat", get(api_flat_summary_handler))
+        .route("/orgs", get(api_orgs_handler))
+        .route("/orgs/:org/matrix", get(api_org_matrix_handler))
+        .route("/teams/:team/summary", get(api_team_summary_handler))
+        .fallback(api_not_found)
+}
+
+async fn api_not_found(OriginalUri(uri): OriginalUri) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({ "error": "not_found", "path": uri.path() })),
+    )
 }
 
 async fn root_handler(db: Extension<PgPool>) -> Result<Html<String>, AppError> {
-    let resp = db::summary::fetch_table(&*db).await?;
+    root_page(&db, *MAX_RENDERED_REPOS).await
+}
+
+/// Renders the root page listing the latest summary of up to `max_repos` repos
+async fn root_page(db: &PgPool, max_repos: usize) -> Result<Html<String>, AppError> {
+    let mut resp = db::summary::fetch_table(db, &TableOptions::default()).await?;
+    resp.retain(|entry| !is_hidden(&HIDDEN_REPOS, entry));
+
+    let truncated = resp.len() > max_repos;
+    resp.truncate(max_repos);
 
     let mut orgs: HashMap<String, Vec<SummaryTableEntry>> = HashMap::new();
     for entry in resp {
@@ -126,27 +1211,3881 @@ async fn root_handler(db: Extension<PgPool>) -> Result<Html<String>, AppError> {
         }
     }
 
+    let mut descriptions: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for (org, repo, description) in db::repo_info::fetch_descriptions(db).await? {
+        descriptions
+            .entry(org)
+            .or_default()
+            .insert(repo, description);
+    }
+
     let orgs: Vec<GiteaOrg> = orgs
         .into_iter()
-        .map(|(k, v)| GiteaOrg { name: k, repos: v })
+        .map(|(k, v)| GiteaOrg {
+            descriptions: descriptions.remove(&k).unwrap_or_default(),
+            name: k,
+            repos: v,
+        })
         .collect();
 
-    tracing::error!("{:?}", orgs);
-
-    let mut context = tera::Context::new();
+    let mut context = base_context();
     context.insert("orgs", &orgs);
+    context.insert("truncated", &truncated);
+    context.insert("max_rendered_repos", &max_repos);
+
+    render_page("base.html", &context)
+}
+
+/// Returns the latest summary of each repo as JSON
+async fn api_summary_handler(
+    db: Extension<PgPool>,
+    Query(query): Query<SummaryQuery>,
+) -> Result<Response, AppError> {
+    let options = TableOptions {
+        label: query.label.as_deref(),
+        sort: query.sort,
+        dir: query.dir,
+        min_uploads: query.min_uploads,
+    };
+    let mut resp = db::summary::fetch_table(&db, &options).await?;
+    resp.retain(|entry| !is_hidden(&HIDDEN_REPOS, entry));
 
-    let output = TEMPLATES.render("base.html", &context)?;
+    if query.flat {
+        let flat: Vec<FlatSummary> = resp
+            .iter()
+            .filter_map(|entry| parse_or_warn(entry, |e| FlatSummary::try_from(e)))
+            .collect();
+        let skipped = resp.len() - flat.len();
 
-    Ok(Html::from(output))
+        return Ok(([(SKIPPED_ROWS_HEADER, skipped.to_string())], Json(flat)).into_response());
+    }
+
+    Ok(Json(resp).into_response())
 }
 
-async fn summary_handler(
+/// Returns the latest summary of each repo as a flat array of percentages, for table UIs. Same as
+/// `/api/summary?flat=true`, accepting the same filters and sorting.
+async fn api_flat_summary_handler(
     db: Extension<PgPool>,
-    Path((org, repo)): Path<(String, String)>,
-    Json(payload): Json<CoverageSummary>,
-) -> Result<(), AppError> {
-    db::summary::insert_into_table(&*db, &org, &repo, &payload)
-        .await
-        .map_err(|e| e.into())
+    Query(mut query): Query<SummaryQuery>,
+) -> Result<Response, AppError> {
+    query.flat = true;
+
+    api_summary_handler(db, Query(query)).await
+}
+
+/// Returns the latest coverage of every repo in an org alongside its change since the previous
+/// summary, worst coverage first
+async fn api_org_matrix_handler(
+    db: Extension<PgPool>,
+    Path(org): Path<String>,
+) -> Result<Response, AppError> {
+    let recent = db::summary::fetch_org_recent(&db, &org, 2).await?;
+
+    if recent.is_empty() {
+        return Err(AppError::NotFound(format!("No coverage found for {org}")));
+    }
+
+    let mut rows = Vec::new();
+    let mut skipped = 0;
+
+    // Each repo's summaries are adjacent, newest first
+    for summaries in recent.chunk_by(|a, b| a.repo == b.repo) {
+        let Some(latest) = parse_or_warn(&summaries[0], |e| e.coverage_summary()) else {
+            skipped += 1;
+            continue;
+        };
+        // A corrupt previous summary only loses the deltas
+        let previous = summaries
+            .get(1)
+            .and_then(|entry| parse_or_warn(entry, |e| e.coverage_summary()));
+
+        let delta = |metric: fn(&CoverageSummary) -> f64| {
+            previous
+                .as_ref()
+                .map(|p| db::summary::round_percent(metric(&latest) - metric(p)))
+        };
+
+        rows.push(MatrixRow {
+            repo: summaries[0].repo.clone(),
+            insert_time: summaries[0].insert_time.timestamp(),
+            overall_percent: latest.overall_percent(),
+            line_percent: latest.line.percent,
+            branch_percent: latest.branch.percent,
+            function_percent: latest.function.percent,
+            line_delta: delta(|s| s.line.percent),
+            branch_delta: delta(|s| s.branch.percent),
+            function_delta: delta(|s| s.function.percent),
+        });
+    }
+
+    rows.sort_by(|a, b| a.overall_percent.total_cmp(&b.overall_percent));
+
+    Ok(([(SKIPPED_ROWS_HEADER, skipped.to_string())], Json(rows)).into_response())
+}
+
+/// Returns the latest coverage of every repo in a team's orgs merged into one summary
+async fn api_team_summary_handler(
+    db: Extension<PgPool>,
+    Path(team): Path<String>,
+) -> Result<Response, AppError> {
+    let orgs = TEAMS
+        .get(&team)
+        .ok_or_else(|| AppError::NotFound(format!("No team named {team}")))?;
+
+    let latest = db::summary::fetch_table(&db, &TableOptions::default()).await?;
+    let team_latest: Vec<&SummaryTableEntry> =
+        latest.iter().filter(|e| orgs.contains(&e.org)).collect();
+
+    let summaries: Vec<CoverageSummary> = team_latest
+        .iter()
+        .filter_map(|entry| parse_or_warn(entry, |e| e.coverage_summary()))
+        .collect();
+    let skipped = team_latest.len() - summaries.len();
+
+    let merged = CoverageSummary::merge(&summaries)
+        .ok_or_else(|| anyhow::anyhow!("Coverage counts of team {team} overflow"))?;
+
+    let body = TeamSummary {
+        team,
+        orgs: orgs.clone(),
+        repos: summaries.len(),
+        overall_percent: merged.overall_percent(),
+        line_percent: merged.line.percent,
+        branch_percent: merged.branch.percent,
+        function_percent: merged.function.percent,
+    };
+
+    Ok(([(SKIPPED_ROWS_HEADER, skipped.to_string())], Json(body)).into_response())
+}
+
+/// Parses a stored summary with `parse`, logging and returning `None` if its coverage is corrupt so
+/// a single bad row doesn't fail a whole listing
+fn parse_or_warn<T>(
+    entry: &SummaryTableEntry,
+    parse: impl FnOnce(&SummaryTableEntry) -> Result<T, serde_json::Error>,
+) -> Option<T> {
+    match parse(entry) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            tracing::warn!(
+                "Skipping corrupt coverage of {}/{} stored at {}: {}",
+                entry.org,
+                entry.repo,
+                entry.insert_time,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Returns an overview of everything stored, cached for `STATS_CACHE_TTL`
+async fn api_stats_handler(
+    db: Extension<PgPool>,
+    cache: Extension<StatsCache>,
+) -> Result<Json<db::Stats>, AppError> {
+    let mut cache = cache.lock().await;
+
+    if let Some((fetched, stats)) = cache.as_ref() {
+        if fetched.elapsed() < STATS_CACHE_TTL {
+            return Ok(Json(stats.clone()));
+        }
+    }
+
+    let stats = db::fetch_stats(&db).await?;
+    *cache = Some((Instant::now(), stats.clone()));
+
+    Ok(Json(stats))
+}
+
+/// Lists every org with its number of repos and when it was last updated, most recently active
+/// first, e.g. for an org selector
+async fn api_orgs_handler(
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<db::summary::OrgOverview>>, AppError> {
+    Ok(Json(db::summary::fetch_org_overview(&db).await?))
+}
+
+/// Runs the checks an upload has to pass before it is stored
+fn validate_upload(upload: &SummaryUpload) -> Result<(), AppError> {
+    upload
+        .coverage
+        .validate()
+        .map_err(AppError::InvalidCoverage)?;
+
+    if let Some(build_url) = &upload.build_url {
+        if !is_web_url(build_url) {
+            return Err(AppError::InvalidCoverage(vec![format!(
+                "build_url ({build_url}) is not an http(s) URL"
+            )]));
+        }
+    }
+
+    if upload.coverage.is_empty() && !*ALLOW_EMPTY_COVERAGE {
+        return Err(AppError::EmptyCoverage);
+    }
+
+    Ok(())
+}
+
+/// Checks `url` is an absolute http(s) URL, so it's safe for dashboards to link to
+fn is_web_url(url: &str) -> bool {
+    match url::Url::parse(url) {
+        Ok(url) => matches!(url.scheme(), "http" | "https"),
+        Err(_) => false,
+    }
+}
+
+/// Parses a comma-separated list of orgs, ignoring surrounding whitespace and empty entries
+fn parse_org_allowlist(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|org| !org.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parses a comma-separated list of `org/repo`s, ignoring surrounding whitespace and any entry
+/// without a `/`
+fn parse_hidden_repos(list: &str) -> HashSet<(String, String)> {
+    list.split(',')
+        .filter_map(|entry| entry.trim().split_once('/'))
+        .map(|(org, repo)| (org.to_string(), repo.to_string()))
+        .collect()
+}
+
+/// Checks whether a summary's repo is left out of listings under `hidden`, normally `$HIDDEN_REPOS`
+fn is_hidden(hidden: &HashSet<(String, String)>, entry: &SummaryTableEntry) -> bool {
+    hidden
+        .iter()
+        .any(|(org, repo)| *org == entry.org && *repo == entry.repo)
+}
+
+/// Checks whether `org` may upload coverage under `allowlist`, normally `$ORG_ALLOWLIST`
+fn org_allowed(allowlist: Option<&HashSet<String>>, org: &str) -> bool {
+    match allowlist {
+        Some(allowlist) => allowlist.contains(org),
+        None => true,
+    }
+}
+
+/// Checks whether `org`/`repo` is already known, either through a reports directory or a stored summary
+async fn repo_is_known(db: &PgPool, org: &str, repo: &str) -> Result<bool, AppError> {
+    if std::path::Path::new(REPORTS_DIR)
+        .join(org)
+        .join(repo)
+        .is_dir()
+    {
+        return Ok(true);
+    }
+
+    Ok(db::summary::repo_exists(db, org, repo).await?)
+}
+
+/// Checks an upload's `X-Coverage-Signature` is the HMAC-SHA256 of its body keyed with the repo's
+/// secret. Repos without a secret accept unsigned uploads.
+async fn verify_signature<E>(
+    db: &PgPool,
+    org: &str,
+    repo: &str,
+    upload: &Signed<E>,
+) -> Result<(), AppError> {
+    match db::repo_secrets::fetch(db, org, repo).await? {
+        Some(secret) => check_signature(&secret, upload),
+        None => Ok(()),
+    }
+}
+
+/// Checks a change to a repo's settings is allowed: admins may always make it, anyone else needs to
+/// sign the request with the repo's secret. Unlike uploads, repos without a secret only accept
+/// changes from admins.
+async fn authorize_repo_change<E>(
+    admin: Option<AdminAuth>,
+    db: &PgPool,
+    org: &str,
+    repo: &str,
+    request: &Signed<E>,
+) -> Result<(), AppError> {
+    if admin.is_some() {
+        return Ok(());
+    }
+
+    match db::repo_secrets::fetch(db, org, repo).await? {
+        Some(secret) => check_signature(&secret, request),
+        None => Err(AppError::Unauthorized),
+    }
+}
+
+/// Checks a request's `X-Coverage-Signature` is the HMAC-SHA256 of its body keyed with `secret`
+fn check_signature<E>(secret: &str, upload: &Signed<E>) -> Result<(), AppError> {
+    let signature = upload
+        .signature
+        .as_deref()
+        .and_then(|v| v.strip_prefix("sha256="))
+        .and_then(decode_hex)
+        .ok_or(AppError::InvalidSignature)?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+    mac.update(&upload.body);
+
+    // Compares in constant time, so the signature can't be guessed byte by byte
+    mac.verify_slice(&signature)
+        .map_err(|_| AppError::InvalidSignature)
+}
+
+/// Decodes a hex string, e.g. a signature, or `None` if it isn't valid hex. An odd length fails as
+/// the last pair is cut short.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` would also take a sign, e.g. `+1`
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Extractor for the state storing a summary touches besides the DB, i.e. the caches and everything
+/// that's told about it, so each upload handler doesn't have to list it all
+#[derive(Clone)]
+struct UploadSinks {
+    latest_cache: LatestCache,
+    events: Events,
+    batcher: Batcher,
+    gitea: Gitea,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for UploadSinks
+where
+    S: Send + Sync,
+{
+    type Rejection = ExtensionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(latest_cache) = Extension::from_request_parts(parts, state).await?;
+        let Extension(events) = Extension::from_request_parts(parts, state).await?;
+        let Extension(batcher) = Extension::from_request_parts(parts, state).await?;
+        let Extension(gitea) = Extension::from_request_parts(parts, state).await?;
+
+        Ok(UploadSinks {
+            latest_cache,
+            events,
+            batcher,
+            gitea,
+        })
+    }
+}
+
+/// Validates and stores a summary upload, acknowledging what was stored. Failed uploads are
+/// recorded for `admin_failures_handler`, so it's possible to tell why a repo's coverage is missing.
+async fn store_summary(
+    db: &PgPool,
+    sinks: &UploadSinks,
+    org: String,
+    repo: String,
+    upload: SummaryUpload,
+) -> Result<Json<SummaryAck>, AppError> {
+    let payload = serde_json::to_string(&upload)?;
+    let resp = try_store_summary(db, sinks, &org, &repo, upload).await;
+
+    match &resp {
+        Err(e) if e.is_upload_failure() => {
+            record_failed_upload(db, &org, &repo, &e.describe(), &payload).await
+        }
+        _ => {}
+    }
+
+    resp
+}
+
+/// Records an upload that couldn't be stored, keeping only the start of its payload. Failing to
+/// record it is only logged, as the upload has already failed.
+pub(crate) async fn record_failed_upload(
+    db: &PgPool,
+    org: &str,
+    repo: &str,
+    error: &str,
+    payload: &str,
+) {
+    let payload = truncate_on_char_boundary(payload, MAX_FAILURE_PAYLOAD_BYTES);
+
+    if let Err(e) = db::failures::insert(db, org, repo, error, payload).await {
+        tracing::warn!("Failed to record failed upload for {}/{}: {}", org, repo, e);
+    }
+}
+
+/// Cuts `s` down to at most `max_len` bytes without splitting a character
+fn truncate_on_char_boundary(s: &str, max_len: usize) -> &str {
+    let mut end = s.len().min(max_len);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+/// The error reported for an upload that would take `org` over its quota
+fn quota_rejection(org: &str, exceeded: QuotaExceeded) -> AppError {
+    match exceeded {
+        QuotaExceeded::Rate(max) => AppError::TooManyRequests(format!(
+            "Org {org} has reached its quota of {max} uploads per hour"
+        )),
+        QuotaExceeded::Storage(max) => AppError::Forbidden(format!(
+            "Org {org} has reached its quota of {max} stored summaries"
+        )),
+    }
+}
+
+/// Marks `commit` as passing or failing in Gitea depending on whether its coverage met the repo's
+/// thresholds, describing its line coverage. Repos without thresholds always pass.
+async fn report_commit_status(
+    client: &GiteaClient,
+    db: &PgPool,
+    org: &str,
+    repo: &str,
+    commit: &str,
+    coverage: &CoverageSummary,
+) -> anyhow::Result<()> {
+    let thresholds = db::repo_config::fetch(db, org, repo)
+        .await?
+        .unwrap_or_default();
+    let failures = thresholds.failures(coverage, *REGRESSION_TOLERANCE);
+
+    let mut description = format!(
+        "{}% line coverage",
+        db::summary::round_percent(coverage.line.percent)
+    );
+    let state = if failures.is_empty() {
+        StatusState::Success
+    } else {
+        description += &format!(" ({})", failures.join("; "));
+        StatusState::Failure
+    };
+
+    let description = truncate_on_char_boundary(&description, MAX_STATUS_DESCRIPTION_LEN);
+    let status = CommitStatus::new(state, description.to_string());
+
+    client.post_status(org, repo, commit, &status).await
+}
+
+async fn try_store_summary(
+    db: &PgPool,
+    sinks: &UploadSinks,
+    org: &str,
+    repo: &str,
+    mut upload: SummaryUpload,
+) -> Result<Json<SummaryAck>, AppError> {
+    let (org, repo) = (org.to_string(), repo.to_string());
+
+    if !org_allowed(ORG_ALLOWLIST.as_ref(), &org) {
+        return Err(AppError::Forbidden(format!(
+            "Org {org} isn't allowed to upload coverage"
+        )));
+    }
+
+    validate_upload(&upload)?;
+
+    if *STRICT_REPOS && !repo_is_known(db, &org, &repo).await? {
+        return Err(AppError::NotFound(format!(
+            "Unknown repo {org}/{repo}, refusing to create it as $STRICT_REPOS is set"
+        )));
+    }
+
+    let coverage = upload.coverage.recomputed();
+    let overall_percent = upload.coverage.overall_percent();
+    let commit = upload.commit.clone();
+
+    let Inserted {
+        insert_time,
+        previous,
+        replaced,
+    } = match &sinks.batcher {
+        // The time is fixed now so the acknowledgement is accurate, though the row is only written
+        // with the next batch. Earlier uploads may still be queued, so the previous summary and
+        // whether the commit was already stored are only as recent as the last flush.
+        Some(batcher) => {
+            // The quota is only enforced when the batch is inserted, so uploads that clearly won't
+            // fit are turned away now rather than acknowledged and then dropped
+            if let Some(exceeded) = db::quota::check(db, &org).await? {
+                return Err(quota_rejection(&org, exceeded));
+            }
+
+            let insert_time = *upload.insert_time.get_or_insert_with(chrono::Utc::now);
+            let previous = db::summary::fetch_latest(db, &org, &repo).await?;
+            let replaced = match &upload.commit {
+                Some(commit) => db::summary::commit_exists(db, &org, &repo, commit).await?,
+                None => false,
+            };
+            batcher.push(org.clone(), repo.clone(), upload).await?;
+            Inserted {
+                insert_time,
+                previous,
+                replaced,
+            }
+        }
+        None => match db::summary::insert_into_table(db, &org, &repo, &upload).await {
+            Err(db::DbError::QuotaExceeded(exceeded)) => {
+                return Err(quota_rejection(&org, exceeded))
+            }
+            inserted => inserted?,
+        },
+    };
+
+    // A corrupt previous summary is treated like a first upload
+    let line_delta = previous
+        .as_ref()
+        .and_then(|entry| parse_or_warn(entry, |e| e.coverage_summary()))
+        .map(|previous| db::summary::round_percent(coverage.line.percent - previous.line.percent));
+
+    sinks
+        .latest_cache
+        .lock()
+        .await
+        .remove(&(org.clone(), repo.clone()));
+
+    if let (Some(client), Some(commit)) = (&sinks.gitea, commit) {
+        // Like events, statuses are best-effort so Gitea being down never fails an upload
+        match client.try_reserve() {
+            Some(permit) => {
+                let (client, db, org, repo, coverage) = (
+                    client.clone(),
+                    db.clone(),
+                    org.clone(),
+                    repo.clone(),
+                    coverage.clone(),
+                );
+
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    if let Err(e) =
+                        report_commit_status(&client, &db, &org, &repo, &commit, &coverage).await
+                    {
+                        tracing::warn!(
+                            "Failed to post commit status for {}/{}@{}: {}",
+                            org,
+                            repo,
+                            commit,
+                            e
+                        );
+                    }
+                });
+            }
+            None => tracing::warn!(
+                "Too many commit statuses pending, dropping the one for {}/{}@{}",
+                org,
+                repo,
+                commit
+            ),
+        }
+    }
+
+    if let Some(publisher) = sinks.events.clone() {
+        let event = SummaryEvent {
+            org: org.clone(),
+            repo: repo.clone(),
+            insert_time: insert_time.timestamp(),
+            coverage,
+        };
+
+        events::spawn_publish(publisher, event);
+    }
+
+    Ok(Json(SummaryAck {
+        overall_percent,
+        change: line_delta.map(|delta| CoverageChange::of(delta, *REGRESSION_TOLERANCE)),
+        line_delta,
+        replaced,
+        insert_time: insert_time.timestamp(),
+        org,
+        repo,
+    }))
+}
+
+async fn summary_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    sinks: UploadSinks,
+    Path((org, repo)): Path<(String, String)>,
+    upload: Signed<JsonUpload<SummaryUpload>>,
+) -> Result<Json<SummaryAck>, AppError> {
+    verify_signature(&db, &org, &repo, &upload).await?;
+    let JsonUpload(payload) = upload.inner;
+
+    // Only admins may backfill, so clients can't fake a repo's timeline
+    if payload.insert_time.is_some() && admin.is_none() {
+        return Err(AppError::Unauthorized);
+    }
+
+    store_summary(&db, &sinks, org, repo, payload).await
+}
+
+/// Creates or replaces the summary of a specific commit, so retrying an upload is harmless. Responds
+/// with `201 Created` for a new commit and `200 OK` for a replaced one.
+async fn commit_summary_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    sinks: UploadSinks,
+    Path((org, repo, commit)): Path<(String, String, String)>,
+    upload: Signed<JsonUpload<SummaryUpload>>,
+) -> Result<(StatusCode, Json<SummaryAck>), AppError> {
+    verify_signature(&db, &org, &repo, &upload).await?;
+    let JsonUpload(mut payload) = upload.inner;
+
+    if payload.insert_time.is_some() && admin.is_none() {
+        return Err(AppError::Unauthorized);
+    }
+
+    match &payload.commit {
+        Some(body_commit) if *body_commit != commit => {
+            return Err(AppError::BadRequest(format!(
+                "The body's commit {body_commit:?} doesn't match the path's {commit:?}"
+            )))
+        }
+        _ => payload.commit = Some(commit),
+    }
+
+    let ack = store_summary(&db, &sinks, org, repo, payload).await?;
+    let status = if ack.replaced {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+
+    Ok((status, ack))
+}
+
+/// Returns the latest summary of a repo with its coverage flattened to percentages, cached for
+/// `LATEST_CACHE_TTL`
+async fn latest_summary_handler(
+    db: Extension<PgPool>,
+    latest_cache: Extension<LatestCache>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<FlatSummary>, AppError> {
+    let key = (org.clone(), repo.clone());
+
+    if let Some((fetched, flat)) = latest_cache.lock().await.get(&key) {
+        if fetched.elapsed() < *LATEST_CACHE_TTL {
+            return Ok(Json(flat.clone()));
+        }
+    }
+
+    // The lock isn't held while fetching, so a slow query for one repo doesn't hold up the rest
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+    let flat = FlatSummary::try_from(&entry)?;
+
+    latest_cache
+        .lock()
+        .await
+        .insert(key, (Instant::now(), flat.clone()));
+
+    Ok(Json(flat))
+}
+
+/// Returns which metrics a repo measures, i.e. those with cases in its latest summary, so UIs can
+/// hide the rest
+async fn available_metrics_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+    let coverage = entry.coverage_summary()?;
+
+    Ok(Json(json!({
+        "line": coverage.line.total > 0,
+        "branch": coverage.branch.total > 0,
+        "function": coverage.function.total > 0,
+    })))
+}
+
+/// Returns the GCOV JSON format and GCC versions that produced a repo's latest coverage. Either is
+/// null if the coverage came from a GCOV too old to report it.
+async fn tool_info_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<ToolInfo>, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    Ok(Json(entry.tool))
+}
+
+/// Returns the latest summary of a repo as plain text, e.g. for reading with curl in a terminal
+async fn plaintext_summary_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<String, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage yet for {org}/{repo}\n")))?;
+    let coverage = entry.coverage_summary()?;
+
+    let mut out = String::new();
+    for (name, metric) in [
+        ("line", &coverage.line),
+        ("branch", &coverage.branch),
+        ("function", &coverage.function),
+    ] {
+        out += &format!(
+            "{:<10}{}% ({}/{})\n",
+            format!("{name}:"),
+            db::summary::round_percent(metric.percent),
+            metric.covered,
+            metric.total
+        );
+    }
+
+    Ok(out)
+}
+
+/// Returns the latest coverage uploaded for a pull request of a repo
+async fn pr_summary_handler(
+    db: Extension<PgPool>,
+    Path((org, repo, number)): Path<(String, String, i32)>,
+) -> Result<Json<FlatSummary>, AppError> {
+    let entry = db::summary::fetch_latest_for_pr(&db, &org, &repo, number)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("No coverage found for {org}/{repo} PR #{number}"))
+        })?;
+
+    Ok(Json(FlatSummary::try_from(&entry)?))
+}
+
+/// Stores a summary uploaded as form fields rather than JSON
+async fn form_summary_handler(
+    db: Extension<PgPool>,
+    sinks: UploadSinks,
+    Path((org, repo)): Path<(String, String)>,
+    upload: Signed<Form<FormUpload>>,
+) -> Result<Json<SummaryAck>, AppError> {
+    verify_signature(&db, &org, &repo, &upload).await?;
+    let Form(form) = upload.inner;
+
+    store_summary(&db, &sinks, org, repo, form.into()).await
+}
+
+/// Merges several summaries, e.g. one per test binary, and stores the result as a single summary
+async fn merge_summary_handler(
+    db: Extension<PgPool>,
+    sinks: UploadSinks,
+    Path((org, repo)): Path<(String, String)>,
+    upload: Signed<JsonUpload<Vec<CoverageSummary>>>,
+) -> Result<Json<SummaryAck>, AppError> {
+    verify_signature(&db, &org, &repo, &upload).await?;
+    let JsonUpload(summaries) = upload.inner;
+
+    let errors: Vec<String> = summaries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, summary)| summary.validate().err().map(|e| (i, e)))
+        .flat_map(|(i, errors)| errors.into_iter().map(move |e| format!("[{i}] {e}")))
+        .collect();
+    if !errors.is_empty() {
+        return Err(AppError::InvalidCoverage(errors));
+    }
+
+    let coverage = CoverageSummary::merge(&summaries).ok_or_else(|| {
+        AppError::InvalidCoverage(vec!["merged counts are too large to store".to_string()])
+    })?;
+
+    let upload = SummaryUpload {
+        coverage,
+        tool: Default::default(),
+        labels: Vec::new(),
+        commit: None,
+        pr_number: None,
+        build_url: None,
+        branch: None,
+        insert_time: None,
+    };
+
+    store_summary(&db, &sinks, org, repo, upload).await
+}
+
+/// Validates a summary upload exactly as `summary_handler` would, without storing it. A payload of
+/// the wrong shape, e.g. missing a count, is reported like any other invalid coverage rather than
+/// with the plain-text rejection the JSON extractor would give.
+async fn validate_summary_handler(
+    JsonUpload(payload): JsonUpload<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let payload: SummaryUpload = serde_json::from_value(payload)
+        .map_err(|e| AppError::InvalidCoverage(vec![e.to_string()]))?;
+    validate_upload(&payload)?;
+
+    Ok(Json(json!({ "valid": true })))
+}
+
+/// Moves all of a repo's data to a new org/repo, e.g. after a transfer between Gitea orgs. Responds
+/// with `409 Conflict` if the target already has data the move would clash with.
+async fn admin_move_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    latest_cache: Extension<LatestCache>,
+    Json(req): Json<MoveRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    for name in [&req.from_org, &req.from_repo, &req.to_org, &req.to_repo] {
+        validate_identifier(name).map_err(AppError::BadRequest)?;
+    }
+
+    let moved = match db::move_repo(
+        &db,
+        &req.from_org,
+        &req.from_repo,
+        &req.to_org,
+        &req.to_repo,
+    )
+    .await
+    {
+        Ok(moved) => moved,
+        // The whole move is rolled back, so neither repo is left half moved
+        Err(db::DbError::Sqlx(sqlx::Error::Database(e))) if e.is_unique_violation() => {
+            let clash = e
+                .table()
+                .map(|table| format!(" in {table}"))
+                .unwrap_or_default();
+            return Err(AppError::Conflict(format!(
+                "{}/{} already has data{}, delete or move it first",
+                req.to_org, req.to_repo, clash
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut latest_cache = latest_cache.lock().await;
+    latest_cache.remove(&(req.from_org, req.from_repo));
+    latest_cache.remove(&(req.to_org, req.to_repo));
+
+    Ok(Json(json!({ "moved": moved })))
+}
+
+/// Exposes the latest coverage of a repo as OpenMetrics gauges
+async fn openmetrics_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    let body = metrics::format_openmetrics(&org, &repo, &entry.coverage_summary()?);
+
+    Ok((
+        [(header::CONTENT_TYPE, metrics::OPENMETRICS_CONTENT_TYPE)],
+        body,
+    )
+        .into_response())
+}
+
+/// Exposes the latest coverage of a repo in the Prometheus text format, ready for a CI step to push
+/// to a Pushgateway
+async fn pushgateway_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    let body = metrics::format_pushgateway(&org, &repo, &entry.coverage_summary()?);
+
+    Ok((
+        [(header::CONTENT_TYPE, metrics::PROMETHEUS_TEXT_CONTENT_TYPE)],
+        body,
+    )
+        .into_response())
+}
+
+/// Sets the stored coverage gate thresholds of a repo, which needs the admin token or a signature
+/// from the repo's secret
+async fn config_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    request: Signed<Json<RepoConfig>>,
+) -> Result<(), AppError> {
+    authorize_repo_change(admin, &db, &org, &repo, &request).await?;
+    let Json(config) = request.inner;
+
+    db::repo_config::upsert(&db, &org, &repo, &config).await?;
+
+    Ok(())
+}
+
+/// Sets the description of a repo shown on the summary page, which needs the admin token or a
+/// signature from the repo's secret
+async fn info_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    request: Signed<Json<RepoInfo>>,
+) -> Result<(), AppError> {
+    authorize_repo_change(admin, &db, &org, &repo, &request).await?;
+    let Json(info) = request.inner;
+
+    if info.description.chars().count() > MAX_DESCRIPTION_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Description must be at most {MAX_DESCRIPTION_LEN} characters"
+        )));
+    }
+
+    db::repo_info::upsert(&db, &org, &repo, &info).await?;
+
+    Ok(())
+}
+
+/// Sets the coverage goals of a repo, which needs the admin token or a signature from the repo's
+/// secret
+async fn set_goals_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    request: Signed<Json<CoverageGoals>>,
+) -> Result<(), AppError> {
+    authorize_repo_change(admin, &db, &org, &repo, &request).await?;
+    let Json(goals) = request.inner;
+
+    db::goals::upsert(&db, &org, &repo, &goals).await?;
+
+    Ok(())
+}
+
+/// Returns the latest coverage of each metric of a repo alongside its goal and how far short of the
+/// goal it is. The goal and gap are null for metrics without a goal.
+async fn goals_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+    let coverage = entry.coverage_summary()?;
+    let goals = db::goals::fetch(&db, &org, &repo)
+        .await?
+        .unwrap_or_default();
+
+    let progress = |current: f64, goal: Option<f64>| {
+        json!({
+            "current": db::summary::round_percent(current),
+            "goal": goal,
+            "gap": goal.map(|goal| db::summary::round_percent((goal - current).max(0.0))),
+        })
+    };
+
+    Ok(Json(json!({
+        "line": progress(coverage.line.percent, goals.line_goal),
+        "branch": progress(coverage.branch.percent, goals.branch_goal),
+        "function": progress(coverage.function.percent, goals.function_goal),
+    })))
+}
+
+/// Marks the latest summary of a repo as a named baseline, e.g. the last release, replacing any
+/// baseline already using the name. Only admins may set baselines, as the request has no body a
+/// repo secret could sign.
+async fn set_baseline_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<BaselineQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    if query.name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Baseline name must not be empty".to_string(),
+        ));
+    }
+
+    let summary_time = db::baselines::set_to_latest(&db, &org, &repo, &query.name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    Ok(Json(json!({
+        "name": query.name,
+        "insert_time": summary_time.timestamp(),
+    })))
+}
+
+/// Describes the change of a metric from `baseline` to `current`, as percentages
+fn metric_delta(current: f64, baseline: f64) -> serde_json::Value {
+    json!({
+        "current": db::summary::round_percent(current),
+        "baseline": db::summary::round_percent(baseline),
+        "delta": db::summary::round_percent(current - baseline),
+        "change": CoverageChange::of(current - baseline, *REGRESSION_TOLERANCE),
+    })
+}
+
+/// Compares two summaries sent in the request, e.g. a local run against a stored one, without
+/// storing either
+async fn compare_handler(
+    JsonUpload(req): JsonUpload<CompareRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let errors: Vec<String> = [("baseline", &req.baseline), ("current", &req.current)]
+        .into_iter()
+        .filter_map(|(name, summary)| summary.validate().err().map(|e| (name, e)))
+        .flat_map(|(name, errors)| errors.into_iter().map(move |e| format!("{name}: {e}")))
+        .collect();
+    if !errors.is_empty() {
+        return Err(AppError::InvalidCoverage(errors));
+    }
+
+    let delta = |metric: fn(&CoverageSummary) -> f64| {
+        metric_delta(metric(&req.current), metric(&req.baseline))
+    };
+
+    Ok(Json(json!({
+        "line": delta(|s| s.line.percent),
+        "branch": delta(|s| s.branch.percent),
+        "function": delta(|s| s.function.percent),
+    })))
+}
+
+/// Returns the latest coverage of each metric of a repo alongside its coverage in a named baseline
+/// and the change since then
+async fn delta_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<DeltaQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let baseline = db::baselines::fetch(&db, &org, &repo, &query.baseline)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No baseline named {:?} for {org}/{repo}",
+                query.baseline
+            ))
+        })?;
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    let current = entry.coverage_summary()?;
+    let previous = baseline.coverage_summary()?;
+
+    let delta =
+        |metric: fn(&CoverageSummary) -> f64| metric_delta(metric(&current), metric(&previous));
+
+    Ok(Json(json!({
+        "baseline": query.baseline,
+        "baseline_time": baseline.summary_time.timestamp(),
+        "insert_time": entry.insert_time.timestamp(),
+        "line": delta(|s| s.line.percent),
+        "branch": delta(|s| s.branch.percent),
+        "function": delta(|s| s.function.percent),
+    })))
+}
+
+/// Checks the latest coverage of a repo against the thresholds in the query, falling back to the
+/// repo's stored thresholds for any not given
+async fn check_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<CheckQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let entry = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    let stored = db::repo_config::fetch(&db, &org, &repo)
+        .await?
+        .unwrap_or_default();
+
+    let thresholds = RepoConfig {
+        line_threshold: query.line.or(stored.line_threshold),
+        branch_threshold: query.branch.or(stored.branch_threshold),
+        function_threshold: query.function.or(stored.function_threshold),
+    };
+
+    let failures = thresholds.failures(&entry.coverage_summary()?, *REGRESSION_TOLERANCE);
+
+    Ok(Json(json!({
+        "passed": failures.is_empty(),
+        "thresholds": thresholds,
+        "failures": failures,
+    })))
+}
+
+/// Returns the latest coverage of each branch of a repo, keyed by branch name
+async fn by_branch_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let latest = db::summary::fetch_latest_per_branch(&db, &org, &repo).await?;
+
+    let branches: BTreeMap<String, FlatSummary> = latest
+        .iter()
+        .filter_map(|entry| {
+            let flat = parse_or_warn(entry, |e| FlatSummary::try_from(e))?;
+            Some((entry.branch.clone()?, flat))
+        })
+        .collect();
+    let skipped = latest.len() - branches.len();
+
+    Ok(([(SKIPPED_ROWS_HEADER, skipped.to_string())], Json(branches)).into_response())
+}
+
+/// Returns how fast a repo's line coverage has been changing over the window, as the slope of a line
+/// fitted through its history in percentage points per day. The change is classified by what the
+/// slope adds up to over the whole window, as `REGRESSION_TOLERANCE` is in percentage points.
+async fn velocity_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<VelocityQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let history = db::summary::fetch_history(&db, &org, &repo).await?;
+    if history.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No coverage found for {org}/{repo}"
+        )));
+    }
+
+    let since = query
+        .window
+        .start(sqlx::types::chrono::Utc::now())
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("Window {} reaches too far back", query.window))
+        })?;
+
+    let points: Vec<(i64, f64)> = history
+        .iter()
+        .filter(|entry| entry.insert_time >= since)
+        .filter_map(|entry| {
+            let coverage = parse_or_warn(entry, |e| e.coverage_summary())?;
+            Some((entry.insert_time.timestamp(), coverage.line.percent))
+        })
+        .collect();
+
+    let slope = velocity::slope_per_day(&points);
+
+    Ok(Json(json!({
+        "window": query.window,
+        "slope_per_day": slope.map(db::summary::round_percent),
+        "direction": slope.map(|slope| {
+            CoverageChange::of(slope * f64::from(query.window.days), *REGRESSION_TOLERANCE)
+        }),
+    })))
+}
+
+/// Returns the min, max, mean, median and standard deviation of a repo's line coverage over its
+/// whole history, for a quick sense of how volatile it is. The statistics are null without history.
+async fn trend_stats_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let history = db::summary::fetch_history(&db, &org, &repo).await?;
+
+    let percents: Vec<f64> = history
+        .iter()
+        .filter_map(|entry| {
+            let coverage = parse_or_warn(entry, |e| e.coverage_summary())?;
+            Some(coverage.line.percent)
+        })
+        .collect();
+
+    let stats = stats::Stats::of(&percents).map(|s| s.rounded(db::summary::round_percent));
+
+    Ok(Json(json!({
+        "count": percents.len(),
+        "line": stats,
+    })))
+}
+
+/// Attaches a note to the latest summary of a repo, which needs the admin token or a signature from
+/// the repo's secret
+async fn add_annotation_handler(
+    admin: Option<AdminAuth>,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    request: Signed<Json<NewAnnotation>>,
+) -> Result<Json<Annotation>, AppError> {
+    authorize_repo_change(admin, &db, &org, &repo, &request).await?;
+    let Json(annotation) = request.inner;
+
+    if annotation.author.chars().count() > MAX_IDENTIFIER_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Author must be at most {MAX_IDENTIFIER_LEN} characters"
+        )));
+    }
+    if annotation.text.chars().count() > MAX_ANNOTATION_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Annotation must be at most {MAX_ANNOTATION_LEN} characters"
+        )));
+    }
+
+    let latest = db::summary::fetch_latest(&db, &org, &repo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No coverage found for {org}/{repo}")))?;
+
+    let stored = db::annotations::insert(&db, &org, &repo, latest.insert_time, &annotation).await?;
+
+    Ok(Json(stored))
+}
+
+/// Lists the notes left on a repo's summaries, oldest first
+async fn list_annotations_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<Vec<Annotation>>, AppError> {
+    Ok(Json(db::annotations::fetch(&db, &org, &repo).await?))
+}
+
+type Percent = fn(&CoverageSummary) -> f64;
+
+/// The metrics drawn on trend charts, with the colour of each line
+const TREND_METRICS: [(&str, [u8; 3], Percent); 3] = [
+    ("line", [31, 119, 180], |s| s.line.percent),
+    ("branch", [255, 127, 14], |s| s.branch.percent),
+    ("function", [44, 160, 44], |s| s.function.percent),
+];
+
+/// Fetches the points of a repo's trend chart, oldest first, as their Unix timestamp and coverage.
+/// Entries that fail to parse are skipped.
+async fn fetch_trend_points(
+    db: &PgPool,
+    org: &str,
+    repo: &str,
+    bucket: Option<Bucket>,
+) -> Result<Vec<(i64, CoverageSummary)>, AppError> {
+    let history = match bucket {
+        Some(bucket) => db::summary::fetch_history_bucketed(db, org, repo, bucket).await?,
+        None => db::summary::fetch_history(db, org, repo).await?,
+    };
+
+    Ok(history
+        .iter()
+        .filter_map(|entry| {
+            let coverage = parse_or_warn(entry, |e| e.coverage_summary())?;
+            Some((entry.insert_time.timestamp(), coverage))
+        })
+        .collect())
+}
+
+/// Renders a page charting a repo's coverage history as an inline SVG, with a line per metric. With
+/// `?bucket=` only the last summary of each day, week or month is charted.
+async fn trend_page_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<TrendQuery>,
+) -> Result<Html<String>, AppError> {
+    let points = fetch_trend_points(&db, &org, &repo, query.bucket).await?;
+
+    let mut context = base_context();
+    context.insert("org", &org);
+    context.insert("repo", &repo);
+    context.insert("width", &TREND_CHART_WIDTH);
+    context.insert("height", &TREND_CHART_HEIGHT);
+
+    if let (Some(first), Some(last)) = (points.first(), points.last()) {
+        let span = (last.0 - first.0) as f64;
+        // Spread the points across the chart by time, with the oldest on the left
+        let x = |t: i64| {
+            if span > 0.0 {
+                (t - first.0) as f64 / span * TREND_CHART_WIDTH
+            } else {
+                TREND_CHART_WIDTH / 2.0
+            }
+        };
+        let y = |percent: f64| TREND_CHART_HEIGHT - percent / 100.0 * TREND_CHART_HEIGHT;
+
+        let series: Vec<serde_json::Value> = TREND_METRICS
+            .into_iter()
+            .map(|(name, colour, metric)| {
+                let polyline: Vec<String> = points
+                    .iter()
+                    .map(|(t, coverage)| format!("{:.1},{:.1}", x(*t), y(metric(coverage))))
+                    .collect();
+
+                json!({
+                    "name": name,
+                    "colour": chart::css_colour(colour),
+                    "points": polyline.join(" "),
+                    "latest": db::summary::round_percent(metric(&last.1)),
+                })
+            })
+            .collect();
+
+        let format_time = |t: i64| {
+            chrono::DateTime::from_timestamp(t, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default()
+        };
+
+        context.insert("series", &series);
+        context.insert("count", &points.len());
+        context.insert("first_time", &format_time(first.0));
+        context.insert("last_time", &format_time(last.0));
+    }
+
+    render_page("trend.html", &context)
+}
+
+/// Renders a repo's coverage history as a PNG line chart, for embedding where SVG or HTML isn't
+/// supported, e.g. slides and emails. Accepts the same `?bucket=` as the trend page. Without any
+/// history only the gridlines are drawn.
+async fn trend_png_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Query(query): Query<TrendQuery>,
+) -> Result<Response, AppError> {
+    let points = fetch_trend_points(&db, &org, &repo, query.bucket).await?;
+
+    let width = TREND_CHART_WIDTH + 2.0 * TREND_PNG_PADDING;
+    let height = TREND_CHART_HEIGHT + 2.0 * TREND_PNG_PADDING;
+
+    let y = |percent: f64| {
+        TREND_PNG_PADDING + TREND_CHART_HEIGHT - percent / 100.0 * TREND_CHART_HEIGHT
+    };
+    let mut lines: Vec<chart::Line> = [0.0, 25.0, 50.0, 75.0, 100.0]
+        .into_iter()
+        .map(|percent| chart::Line {
+            points: vec![
+                (TREND_PNG_PADDING, y(percent)),
+                (TREND_PNG_PADDING + TREND_CHART_WIDTH, y(percent)),
+            ],
+            width: 1,
+            colour: [221, 221, 221],
+        })
+        .collect();
+
+    if let (Some(first), Some(last)) = (points.first(), points.last()) {
+        let span = (last.0 - first.0) as f64;
+        let x = |t: i64| {
+            TREND_PNG_PADDING
+                + if span > 0.0 {
+                    (t - first.0) as f64 / span * TREND_CHART_WIDTH
+                } else {
+                    TREND_CHART_WIDTH / 2.0
+                }
+        };
+
+        for (_, colour, metric) in TREND_METRICS {
+            let points: Vec<(f64, f64)> = points
+                .iter()
+                .map(|(t, coverage)| (x(*t), y(metric(coverage))))
+                .collect();
+            // A lone point is drawn as a dot, which is larger to stand out
+            let width = if points.len() == 1 { 5 } else { 2 };
+
+            lines.push(chart::Line {
+                points,
+                width,
+                colour,
+            });
+        }
+    }
+
+    let png = chart::render_png(width as u32, height as u32, &lines)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/png".to_string()),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={TREND_PNG_MAX_AGE_SECS}"),
+            ),
+        ],
+        png,
+    )
+        .into_response())
+}
+
+/// Exports the full summary history of a repo as a downloadable JSON file
+async fn history_export_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Response, AppError> {
+    let history = db::summary::fetch_history(&db, &org, &repo).await?;
+    let annotations = db::annotations::fetch(&db, &org, &repo).await?;
+
+    let disposition = format!(
+        r#"attachment; filename="{}-{}-history.json""#,
+        org.replace('"', ""),
+        repo.replace('"', "")
+    );
+
+    let body = Json(json!({
+        "org": org,
+        "repo": repo,
+        "exported_at": sqlx::types::chrono::Utc::now().timestamp(),
+        "history": history,
+        "annotations": annotations,
+    }));
+
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], body).into_response())
+}
+
+/// Lists the commits a repo has summaries for, newest first
+async fn commits_handler(
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<Json<Vec<db::summary::CommitEntry>>, AppError> {
+    if !db::summary::repo_exists(&db, &org, &repo).await? {
+        return Err(AppError::NotFound(format!(
+            "No coverage found for {org}/{repo}"
+        )));
+    }
+
+    Ok(Json(db::summary::fetch_commits(&db, &org, &repo).await?))
+}
+
+/// Returns stored summaries exactly as stored, newest first, for troubleshooting. Pages hold up to
+/// `MAX_RAW_SUMMARIES` rows; when there are more, `truncated` is set and `next_cursor` can be passed
+/// back as `?after=` to fetch the next page.
+async fn admin_raw_summaries_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let after = match query.after.as_deref() {
+        Some(token) => Some(
+            db::summary::Cursor::decode(token)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid cursor {token:?}")))?,
+        ),
+        None => None,
+    };
+
+    // Fetch one extra row to tell whether the cap cut anything off
+    let mut rows = db::summary::fetch_page(&db, after, MAX_RAW_SUMMARIES + 1).await?;
+    let truncated = rows.len() as i64 > MAX_RAW_SUMMARIES;
+    rows.truncate(MAX_RAW_SUMMARIES as usize);
+
+    let next_cursor = rows
+        .last()
+        .filter(|_| truncated)
+        .map(|row| db::summary::Cursor::after(row).encode());
+    let summaries: Vec<serde_json::Value> = rows.iter().map(|row| row.entry.raw()).collect();
+
+    Ok(Json(json!({
+        "truncated": truncated,
+        "next_cursor": next_cursor,
+        "summaries": summaries,
+    })))
+}
+
+/// Sets the secret uploads to a repo must be signed with
+async fn admin_set_secret_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+    Json(req): Json<SecretRequest>,
+) -> Result<(), AppError> {
+    if req.secret.is_empty() {
+        return Err(AppError::BadRequest("Secret must not be empty".to_string()));
+    }
+
+    db::repo_secrets::upsert(&db, &org, &repo, &req.secret).await?;
+
+    Ok(())
+}
+
+/// Removes a repo's secret, so its uploads no longer need signing
+async fn admin_delete_secret_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Path((org, repo)): Path<(String, String)>,
+) -> Result<(), AppError> {
+    if !db::repo_secrets::delete(&db, &org, &repo).await? {
+        return Err(AppError::NotFound(format!("{org}/{repo} has no secret")));
+    }
+
+    Ok(())
+}
+
+/// Returns the quota of an org, which is unlimited if none has been set
+async fn admin_quota_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Path(org): Path<String>,
+) -> Result<Json<OrgQuota>, AppError> {
+    Ok(Json(db::quota::fetch(&db, &org).await?.unwrap_or_default()))
+}
+
+/// Sets the quota of an org
+async fn admin_set_quota_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+    Path(org): Path<String>,
+    Json(quota): Json<OrgQuota>,
+) -> Result<(), AppError> {
+    db::quota::upsert(&db, &org, &quota).await?;
+
+    Ok(())
+}
+
+/// Lists the most recent failed uploads, newest first
+async fn admin_failures_handler(
+    _: AdminAuth,
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<FailedUpload>>, AppError> {
+    Ok(Json(
+        db::failures::fetch_recent(&db, MAX_LISTED_FAILURES).await?,
+    ))
+}
+
+/// Streams every stored summary exactly as stored, oldest first, as newline-delimited JSON. Unlike
+/// `admin_raw_summaries_handler` this is uncapped, as rows are sent as they're read.
+async fn admin_export_handler(_: AdminAuth, db: Extension<PgPool>) -> Response {
+    let Extension(db) = db;
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, db::DbError>>(EXPORT_BUFFER_ROWS);
+
+    // The row stream borrows the pool, so it's read on its own task, which the bounded channel
+    // holds back whenever the client is slower than the DB
+    tokio::spawn(async move {
+        // The export is a single query, which would otherwise be cancelled by the statement timeout
+        // partway through, cutting the export short
+        let begin = async {
+            let mut conn = db.begin().await?;
+            db::disable_statement_timeout(&mut conn).await?;
+            Ok::<_, sqlx::Error>(conn)
+        };
+        let mut conn = match begin.await {
+            Ok(conn) => conn,
+            Err(e) => {
+                let _ = tx.send(Err(e.into())).await;
+                return;
+            }
+        };
+        let mut rows = db::summary::stream_all(&mut conn);
+
+        while let Some(row) = rows.next().await {
+            let line = row.map_err(db::DbError::from).and_then(|entry| {
+                let mut line = serde_json::to_vec(&entry.raw())?;
+                line.push(b'\n');
+                Ok(line)
+            });
+
+            // Stop reading once the client has gone away
+            if tx.send(line).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let lines =
+        futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|l| (l, rx)) });
+
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn render_markdown(markdown: &str) -> String {
+        let html = markdown_filter(&tera::Value::from(markdown), &HashMap::new()).unwrap();
+        html.as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn markdown_escapes_raw_html() {
+        let html = render_markdown("<script>alert(1)</script>\n\nhi <b onclick=\"x\">there</b>");
+        assert!(!html.contains("<script"), "{html}");
+        assert!(!html.contains("<b "), "{html}");
+        assert!(html.contains("&lt;script&gt;"), "{html}");
+    }
+
+    #[test]
+    fn markdown_drops_unsafe_link_urls() {
+        let html = render_markdown("[x](javascript:alert(1)) ![y](JavaScript:alert(2))");
+        assert!(!html.to_ascii_lowercase().contains("javascript:"), "{html}");
+
+        let html = render_markdown("[docs](https://example.com/docs) [rel](docs/a:b)");
+        assert!(
+            html.contains(r#"href="https://example.com/docs""#),
+            "{html}"
+        );
+        assert!(html.contains(r#"href="docs/a:b""#), "{html}");
+    }
+
+    #[test]
+    fn validates_identifiers() {
+        assert!(validate_identifier("gcov-server").is_ok());
+        assert!(validate_identifier("a.b_c-1").is_ok());
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN)).is_ok());
+
+        assert!(validate_identifier("").is_err());
+        assert!(validate_identifier(&"a".repeat(MAX_IDENTIFIER_LEN + 1)).is_err());
+        assert!(validate_identifier(".").is_err());
+        assert!(validate_identifier("..").is_err());
+        assert!(validate_identifier("a/b").is_err());
+        assert!(validate_identifier("a b").is_err());
+        assert!(validate_identifier("é").is_err());
+    }
+
+    fn signed(body: &str, signature: Option<String>) -> Signed<()> {
+        Signed {
+            inner: (),
+            body: Bytes::from(body.to_string()),
+            signature,
+        }
+    }
+
+    fn sign(secret: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        let hex: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        format!("sha256={hex}")
+    }
+
+    #[test]
+    fn parses_org_allowlists() {
+        let allowlist = parse_org_allowlist(" alpha,beta , ,gamma,");
+        let expected: HashSet<String> = ["alpha", "beta", "gamma"].map(String::from).into();
+        assert_eq!(allowlist, expected);
+
+        assert!(parse_org_allowlist("").is_empty());
+        assert!(parse_org_allowlist(" , ").is_empty());
+    }
+
+    #[test]
+    fn checks_orgs_against_the_allowlist() {
+        let allowlist = parse_org_allowlist("alpha,beta");
+        assert!(org_allowed(Some(&allowlist), "alpha"));
+        assert!(org_allowed(Some(&allowlist), "beta"));
+        assert!(!org_allowed(Some(&allowlist), "gamma"));
+        // Org names are matched exactly
+        assert!(!org_allowed(Some(&allowlist), "Alpha"));
+
+        // An empty list allows nobody, while no list allows everybody
+        assert!(!org_allowed(Some(&HashSet::new()), "alpha"));
+        assert!(org_allowed(None, "gamma"));
+    }
+
+    #[test]
+    fn parses_hidden_repos() {
+        let hidden = parse_hidden_repos(" org/vendored,org/generated/v2 , nope, ,other/repo,");
+        let expected: HashSet<(String, String)> = [
+            ("org", "vendored"),
+            // Only the first `/` separates the org from the repo
+            ("org", "generated/v2"),
+            ("other", "repo"),
+        ]
+        .map(|(org, repo)| (org.to_string(), repo.to_string()))
+        .into();
+        assert_eq!(hidden, expected);
+
+        assert!(parse_hidden_repos("").is_empty());
+    }
+
+    #[test]
+    fn hides_only_the_listed_repos() {
+        let hidden = parse_hidden_repos("org/vendored");
+        let entry = |org: &str, repo: &str| SummaryTableEntry {
+            insert_time: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            org: org.to_string(),
+            repo: repo.to_string(),
+            coverage: coverage(1, 2),
+            tool: db::summary::ToolInfo::default(),
+            labels: Vec::new(),
+            commit: None,
+            pr_number: None,
+            build_url: None,
+            branch: None,
+        };
+
+        assert!(is_hidden(&hidden, &entry("org", "vendored")));
+        assert!(!is_hidden(&hidden, &entry("org", "app")));
+        // The org and repo must both match
+        assert!(!is_hidden(&hidden, &entry("other", "vendored")));
+        assert!(!is_hidden(&HashSet::new(), &entry("org", "vendored")));
+    }
+
+    fn forwarded(values: &[&str]) -> Option<IpAddr> {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        forwarded_for(&headers)
+    }
+
+    #[test]
+    fn reads_the_proxys_forwarded_for_address() {
+        let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+
+        assert_eq!(forwarded(&["203.0.113.7"]), ip("203.0.113.7"));
+        assert_eq!(forwarded(&[" 2001:db8::1 "]), ip("2001:db8::1"));
+        // A client can send its own header, which the proxy appends to
+        assert_eq!(forwarded(&["10.0.0.1, 203.0.113.7"]), ip("203.0.113.7"));
+        assert_eq!(
+            forwarded(&["1.1.1.1,10.0.0.1 ,203.0.113.7"]),
+            ip("203.0.113.7")
+        );
+        assert_eq!(forwarded(&["10.0.0.1", "203.0.113.7"]), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn ignores_garbage_forwarded_for_headers() {
+        assert_eq!(forwarded(&[]), None);
+        assert_eq!(forwarded(&[""]), None);
+        assert_eq!(forwarded(&["not an address"]), None);
+        assert_eq!(forwarded(&["203.0.113.7,"]), None);
+        assert_eq!(forwarded(&["203.0.113.7:8080"]), None);
+        // Only the proxy's entry counts, so a valid spoofed one doesn't get through
+        assert_eq!(forwarded(&["203.0.113.7, garbage"]), None);
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("00ff10"), Some(vec![0x00, 0xff, 0x10]));
+        assert_eq!(decode_hex("ABcd"), Some(vec![0xab, 0xcd]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("+1"), None);
+        assert_eq!(decode_hex("é1"), None);
+    }
+
+    #[test]
+    fn accepts_matching_signatures() {
+        let body = r#"{"line_covered":1}"#;
+        let upload = signed(body, Some(sign("secret", body)));
+        assert!(check_signature("secret", &upload).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        let body = r#"{"line_covered":1}"#;
+        let valid = sign("secret", body);
+
+        let invalid = [
+            None,
+            Some(sign("other", body)),
+            Some(sign("secret", "{}")),
+            Some(valid.trim_start_matches("sha256=").to_string()),
+            Some(format!("sha256={}", "0".repeat(64))),
+            Some("sha256=not-hex".to_string()),
+        ];
+        for signature in invalid {
+            let upload = signed(body, signature.clone());
+            assert!(
+                matches!(
+                    check_signature("secret", &upload),
+                    Err(AppError::InvalidSignature)
+                ),
+                "{signature:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn safe_urls() {
+        assert!(is_safe_url("https://example.com"));
+        assert!(is_safe_url("mailto:dev@example.com"));
+        assert!(is_safe_url("/org/repo"));
+        assert!(is_safe_url("#top"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("data:text/html,hi"));
+        assert!(!is_safe_url(" vbscript:x"));
+    }
+
+    #[test]
+    fn web_urls() {
+        assert!(is_web_url("https://ci.example.com/build/42"));
+        assert!(is_web_url("http://10.0.0.1:8080/job?id=1"));
+        assert!(!is_web_url("ftp://ci.example.com/build/42"));
+        assert!(!is_web_url("javascript:alert(1)"));
+        assert!(!is_web_url("/build/42"));
+        assert!(!is_web_url("ci.example.com/build/42"));
+        assert!(!is_web_url(""));
+    }
+
+    #[sqlx::test]
+    async fn serves_uploaded_summaries(db: PgPool) {
+        let app = test_app(db).await;
+
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = body_json(resp).await;
+        assert_eq!(body["org"], "org");
+        assert_eq!(body["line_percent"], 50.0);
+
+        let resp = send(&app, get_request("/org/other/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn head_returns_headers_without_a_body(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+
+        let full = body_bytes(send(&app, get_request("/org/repo/summary")).await).await;
+        let resp = send(
+            &app,
+            request(Method::HEAD, "/org/repo/summary", axum::body::Body::empty()),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(
+            resp.headers()[header::CONTENT_LENGTH],
+            full.len().to_string().as_str()
+        );
+        assert!(body_bytes(resp).await.is_empty());
+    }
+
+    async fn validate(app: &Router, body: &serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let resp = send(
+            app,
+            json_request(Method::POST, "/org/repo/summary/validate", body),
+        )
+        .await;
+
+        (resp.status(), body_json(resp).await)
+    }
+
+    #[sqlx::test]
+    async fn validates_uploads_without_storing_them(db: PgPool) {
+        let app = test_app(db).await;
+
+        let (status, body) = validate(&app, &coverage(5, 10)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "valid": true }));
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn validate_reports_inconsistent_coverage(db: PgPool) {
+        let app = test_app(db).await;
+
+        let (status, body) = validate(&app, &coverage(11, 10)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["valid"], false);
+        assert_eq!(
+            body["errors"],
+            json!(["line: covered (11) exceeds total (10)"])
+        );
+    }
+
+    #[sqlx::test]
+    async fn validate_reports_malformed_payloads(db: PgPool) {
+        let app = test_app(db).await;
+
+        let mut malformed = coverage(5, 10);
+        malformed["line_total"] = json!("ten");
+        for body in [json!({ "line_covered": 5 }), malformed, json!([1, 2])] {
+            let (status, resp) = validate(&app, &body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+            assert_eq!(resp["valid"], false, "{body}");
+            assert_eq!(resp["errors"].as_array().unwrap().len(), 1, "{body}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn filters_summaries_by_label(db: PgPool) {
+        let app = test_app(db).await;
+
+        let mut nightly = coverage(5, 10);
+        nightly["labels"] = json!(["nightly", "release-1.2"]);
+        upload(&app, "org", "a", &nightly).await;
+        upload(&app, "org", "b", &coverage(6, 10)).await;
+        // The latest summary of a repo without the label doesn't hide its labelled ones
+        upload(&app, "org", "a", &coverage(7, 10)).await;
+
+        let resp = send(&app, get_request("/api/summary?label=nightly")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = body_json(resp).await;
+        let summaries = body.as_array().unwrap();
+        assert_eq!(summaries.len(), 1, "{body}");
+        assert_eq!(summaries[0]["repo"], "a");
+        assert_eq!(summaries[0]["labels"], json!(["nightly", "release-1.2"]));
+        assert_eq!(summaries[0]["coverage"]["line_covered"], 5);
+
+        let body = body_json(send(&app, get_request("/api/summary?label=flaky")).await).await;
+        assert_eq!(body, json!([]));
+    }
+
+    async fn move_repo(app: &Router, from: (&str, &str), to: (&str, &str)) -> Response {
+        let body = json!({
+            "from_org": from.0,
+            "from_repo": from.1,
+            "to_org": to.0,
+            "to_repo": to.1,
+        });
+
+        send(
+            app,
+            as_admin(json_request(Method::POST, "/admin/move", &body)),
+        )
+        .await
+    }
+
+    #[sqlx::test]
+    async fn moves_a_repos_data(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        upload(&app, "old", "repo", &coverage(5, 10)).await;
+        upload(&app, "old", "repo", &coverage(6, 10)).await;
+        let info = RepoInfo {
+            description: "Moved along".to_string(),
+        };
+        db::repo_info::upsert(&db, "old", "repo", &info)
+            .await
+            .unwrap();
+
+        let resp = move_repo(&app, ("old", "repo"), ("new", "renamed")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_json(resp).await, json!({ "moved": 3 }));
+
+        let resp = send(&app, get_request("/old/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = body_json(send(&app, get_request("/new/renamed/summary")).await).await;
+        assert_eq!(body["line_percent"], 60.0);
+        let descriptions = db::repo_info::fetch_descriptions(&db).await.unwrap();
+        assert_eq!(
+            descriptions,
+            [(
+                "new".to_string(),
+                "renamed".to_string(),
+                "Moved along".to_string()
+            )]
+        );
+    }
+
+    #[sqlx::test]
+    async fn refuses_moves_onto_clashing_data(db: PgPool) {
+        let app = test_app(db).await;
+        let mut commit = coverage(5, 10);
+        commit["commit"] = json!("abc123");
+        upload(&app, "a", "x", &commit).await;
+        upload(&app, "b", "y", &commit).await;
+
+        let resp = move_repo(&app, ("a", "x"), ("b", "y")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        // Nothing was moved
+        let resp = send(&app, get_request("/a/x/summary")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn moving_needs_the_admin_token(db: PgPool) {
+        let app = test_app(db).await;
+        let body = json!({ "from_org": "a", "from_repo": "x", "to_org": "b", "to_repo": "y" });
+
+        let resp = send(&app, json_request(Method::POST, "/admin/move", &body)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn knows_repos_with_stored_summaries(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        assert!(!repo_is_known(&db, "org", "repo").await.unwrap());
+
+        // Without STRICT_REPOS, uploading creates the repo
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+        assert!(repo_is_known(&db, "org", "repo").await.unwrap());
+        assert!(!repo_is_known(&db, "org", "other").await.unwrap());
+        assert!(!repo_is_known(&db, "other", "repo").await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn counts_stored_summaries(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "a", "x", &coverage(5, 10)).await;
+        upload(&app, "a", "x", &coverage(6, 10)).await;
+        upload(&app, "a", "y", &coverage(6, 10)).await;
+        upload(&app, "b", "x", &coverage(6, 10)).await;
+
+        let resp = send(&app, get_request("/api/stats")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(resp).await,
+            json!({ "summaries": 4, "orgs": 2, "repos": 3 })
+        );
+    }
+
+    #[sqlx::test]
+    async fn acknowledges_stored_summaries(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &coverage(5, 10)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ack = body_json(resp).await;
+        assert_eq!(ack["org"], "org");
+        assert_eq!(ack["repo"], "repo");
+        // 9 of the 16 cases across every metric
+        assert_eq!(ack["overall_percent"], 56.25);
+        assert_eq!(ack["line_delta"], serde_json::Value::Null);
+        assert_eq!(ack["change"], serde_json::Value::Null);
+        assert_eq!(ack["replaced"], false);
+        assert!(ack["insert_time"].as_i64().unwrap() > 0);
+
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &coverage(6, 10)),
+        )
+        .await;
+        let ack = body_json(resp).await;
+        assert_eq!(ack["line_delta"], 10.0);
+        assert_eq!(ack["change"], "improved");
+
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &coverage(3, 10)),
+        )
+        .await;
+        let ack = body_json(resp).await;
+        assert_eq!(ack["line_delta"], -30.0);
+        assert_eq!(ack["change"], "regressed");
+
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &coverage(3, 10)),
+        )
+        .await;
+        let ack = body_json(resp).await;
+        assert_eq!(ack["line_delta"], 0.0);
+        assert_eq!(ack["change"], "held");
+    }
+
+    #[test]
+    fn classifies_coverage_changes() {
+        assert_eq!(CoverageChange::of(0.5, 0.1), CoverageChange::Improved);
+        assert_eq!(CoverageChange::of(-0.5, 0.1), CoverageChange::Regressed);
+        // Changes within the tolerance are noise
+        assert_eq!(CoverageChange::of(0.05, 0.1), CoverageChange::Held);
+        assert_eq!(CoverageChange::of(-0.05, 0.1), CoverageChange::Held);
+        assert_eq!(CoverageChange::of(-0.1, 0.1), CoverageChange::Held);
+        assert_eq!(CoverageChange::of(0.0, 0.0), CoverageChange::Held);
+    }
+
+    fn render_root_page(context: &mut tera::Context) -> String {
+        context.insert("orgs", &Vec::<GiteaOrg>::new());
+        context.insert("truncated", &false);
+
+        render_page("base.html", context).unwrap().0
+    }
+
+    // Assumes the SITE_* variables aren't set for the tests
+    #[test]
+    fn pages_fall_back_to_default_branding() {
+        let html = render_root_page(&mut base_context());
+
+        assert!(html.contains("<title>Coverage Summary</title>"), "{html}");
+        assert!(!html.contains("<img"), "{html}");
+        assert!(!html.contains("<footer>"), "{html}");
+    }
+
+    #[test]
+    fn pages_show_configured_branding() {
+        let mut context = base_context();
+        context.insert("site_title", "Acme <Coverage>");
+        context.insert("site_logo_url", "https://example.com/logo.png");
+        context.insert("site_footer", "Run by the build team");
+        let html = render_root_page(&mut context);
+
+        assert!(
+            html.contains("<title>Acme &lt;Coverage&gt;</title>"),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<img src="https:&#x2F;&#x2F;example.com&#x2F;logo.png" alt="logo">"#),
+            "{html}"
+        );
+        assert!(
+            html.contains("<footer>Run by the build team</footer>"),
+            "{html}"
+        );
+    }
+
+    #[sqlx::test]
+    async fn rejects_empty_coverage(db: PgPool) {
+        let app = test_app(db).await;
+        let empty = json!({
+            "branch_covered": 0, "branch_total": 0, "branch_percent": 0.0,
+            "function_covered": 0, "function_total": 0, "function_percent": 0.0,
+            "line_covered": 0, "line_total": 0, "line_percent": 0.0,
+        });
+
+        // Assumes ALLOW_EMPTY_COVERAGE isn't set for the tests
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &empty),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body_string(resp).await.contains("did the test run fail?"));
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn flattens_the_summary_listing(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "repo", &coverage(1, 3)).await;
+
+        let body = body_json(send(&app, get_request("/api/summary?flat=true")).await).await;
+        let summaries = body.as_array().unwrap();
+        assert_eq!(summaries.len(), 1);
+
+        let summary = summaries[0].as_object().unwrap();
+        let mut keys: Vec<&str> = summary.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(
+            keys,
+            [
+                "branch_percent",
+                "function_percent",
+                "insert_time",
+                "line_percent",
+                "org",
+                "repo"
+            ]
+        );
+        assert_eq!(summary["line_percent"], 33.33);
+        assert_eq!(summary["branch_percent"], 50.0);
+        assert_eq!(summary["function_percent"], 75.0);
+    }
+
+    #[tokio::test]
+    async fn unknown_api_paths_get_a_json_404() {
+        let app = lazy_app();
+
+        let resp = send(&app, get_request("/api/nope/nothing")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(
+            body_json(resp).await,
+            json!({ "error": "not_found", "path": "/api/nope/nothing" })
+        );
+
+        // Paths outside /api keep axum's usual empty 404
+        let resp = send(&app, get_request("/a/b/c/d/e")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(body_bytes(resp).await.is_empty());
+    }
+
+    /// A JSON request signed with `secret`, as a client holding the repo's secret would send it
+    fn signed_request(
+        method: Method,
+        uri: &str,
+        body: &serde_json::Value,
+        secret: &str,
+    ) -> Request<axum::body::Body> {
+        let mut req = json_request(method, uri, body);
+        let signature = sign(secret, &body.to_string());
+        req.headers_mut()
+            .insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        req
+    }
+
+    #[sqlx::test]
+    async fn checks_against_stored_thresholds(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+        db::repo_secrets::upsert(&db, "org", "repo", "secret")
+            .await
+            .unwrap();
+
+        let config = json!({ "line_threshold": 60.0 });
+        let resp = send(&app, json_request(Method::PUT, "/org/repo/config", &config)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = send(
+            &app,
+            signed_request(Method::PUT, "/org/repo/config", &config, "secret"),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = body_json(send(&app, get_request("/org/repo/check")).await).await;
+        assert_eq!(body["passed"], false);
+        assert_eq!(body["thresholds"]["line_threshold"], 60.0);
+        assert_eq!(body["failures"], json!(["line: 50 is below 60"]));
+
+        // Query thresholds override the stored ones
+        let body = body_json(send(&app, get_request("/org/repo/check?line=40")).await).await;
+        assert_eq!(body["passed"], true);
+
+        // Setting the config again replaces it
+        let config = json!({ "line_threshold": 45.0, "branch_threshold": 40.0 });
+        let resp = send(
+            &app,
+            signed_request(Method::PUT, "/org/repo/config", &config, "secret"),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let config = db::repo_config::fetch(&db, "org", "repo")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.line_threshold, Some(45.0));
+        assert_eq!(config.branch_threshold, Some(40.0));
+        assert_eq!(config.function_threshold, None);
+    }
+
+    async fn listed_repos(app: &Router, uri: &str) -> Vec<String> {
+        let resp = send(app, get_request(uri)).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+
+        body_json(resp)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|summary| summary["repo"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn sorts_the_summary_listing(db: PgPool) {
+        let app = test_app(db).await;
+        // Uploaded in this order, so by insert time they're b, c, a
+        upload(&app, "org", "b", &coverage_of((2, 10), (9, 10), (5, 10))).await;
+        upload(&app, "org", "c", &coverage_of((9, 10), (5, 10), (2, 10))).await;
+        upload(&app, "org", "a", &coverage_of((5, 10), (2, 10), (9, 10))).await;
+
+        for (field, ascending) in [
+            ("line_percent", ["b", "a", "c"]),
+            ("branch_percent", ["a", "c", "b"]),
+            ("function_percent", ["c", "b", "a"]),
+            ("insert_time", ["b", "c", "a"]),
+            ("repo", ["a", "b", "c"]),
+        ] {
+            let uri = format!("/api/summary?sort={field}&dir=asc");
+            assert_eq!(listed_repos(&app, &uri).await, ascending, "{field}");
+
+            let mut descending = ascending;
+            descending.reverse();
+            let uri = format!("/api/summary?sort={field}&dir=desc");
+            assert_eq!(listed_repos(&app, &uri).await, descending, "{field}");
+        }
+
+        assert_eq!(listed_repos(&app, "/api/summary").await, ["a", "b", "c"]);
+
+        let resp = send(
+            &app,
+            get_request("/api/summary?sort=org%3BDROP%20TABLE%20summary"),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip_upload(body: Vec<u8>) -> Request<axum::body::Body> {
+        let mut req = request(Method::POST, "/org/repo/summary", body);
+        let headers = req.headers_mut();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        headers.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        req
+    }
+
+    #[sqlx::test]
+    async fn accepts_gzipped_uploads(db: PgPool) {
+        let app = test_app(db).await;
+        let body = gzip(coverage(5, 10).to_string().as_bytes());
+
+        let resp = send(&app, gzip_upload(body)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = body_json(send(&app, get_request("/org/repo/summary")).await).await;
+        assert_eq!(body["line_percent"], 50.0);
+    }
+
+    #[sqlx::test]
+    async fn rejects_truncated_gzip(db: PgPool) {
+        let app = test_app(db).await;
+        let mut body = gzip(coverage(5, 10).to_string().as_bytes());
+        body.truncate(body.len() / 2);
+
+        let resp = send(&app, gzip_upload(body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = send(&app, gzip_upload(b"not gzip at all".to_vec())).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn probes_report_a_working_database(db: PgPool) {
+        let app = test_app(db).await;
+
+        for probe in ["/livez", "/readyz"] {
+            let resp = send(&app, get_request(probe)).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{probe}");
+            assert_eq!(body_string(resp).await, "ok");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_readiness_fails_without_a_database() {
+        let app = lazy_app();
+
+        let resp = send(&app, get_request("/livez")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = send(&app, get_request("/readyz")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_string(resp).await, "database unavailable");
+    }
+
+    #[sqlx::test]
+    async fn reuploading_a_commit_replaces_its_summary(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(
+            &app,
+            json_request(Method::PUT, "/org/repo/abc123/summary", &coverage(1, 10)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let resp = send(
+            &app,
+            json_request(Method::PUT, "/org/repo/abc123/summary", &coverage(7, 10)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_json(resp).await["replaced"], true);
+
+        // A POST naming the same commit replaces it too
+        let mut body = coverage(9, 10);
+        body["commit"] = json!("abc123");
+        upload(&app, "org", "repo", &body).await;
+
+        let export = body_json(send(&app, get_request("/org/repo/history.json")).await).await;
+        let history = export["history"].as_array().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["commit"], "abc123");
+        assert_eq!(history[0]["coverage"]["line_covered"], 9);
+    }
+
+    #[sqlx::test]
+    async fn downloads_a_repos_history(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "repo", &coverage(1, 10)).await;
+        upload(&app, "org", "repo", &coverage(2, 10)).await;
+
+        let resp = send(&app, get_request("/org/repo/history.json")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[header::CONTENT_DISPOSITION],
+            r#"attachment; filename="org-repo-history.json""#
+        );
+
+        let export = body_json(resp).await;
+        assert_eq!(export["org"], "org");
+        assert_eq!(export["repo"], "repo");
+        assert!(export["exported_at"].as_i64().unwrap() > 0);
+        assert_eq!(export["annotations"], json!([]));
+
+        let history = export["history"].as_array().unwrap();
+        let covered: Vec<_> = history
+            .iter()
+            .map(|entry| entry["coverage"]["line_covered"].clone())
+            .collect();
+        assert_eq!(covered, [1, 2]);
+    }
+
+    #[sqlx::test]
+    async fn root_page_escapes_org_names(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        // Path validation would refuse this org, so it's stored directly
+        let summary: SummaryUpload = serde_json::from_value(coverage(1, 2)).unwrap();
+        db::summary::insert_into_table(&db, "<script>alert(1)</script>", "repo", &summary)
+            .await
+            .unwrap();
+
+        let resp = send(&app, get_request("/")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let page = body_string(resp).await;
+        assert!(
+            page.contains("&lt;script&gt;alert(1)&lt;&#x2F;script&gt;"),
+            "{page}"
+        );
+        assert!(!page.contains("<script>alert(1)"));
+    }
+
+    #[sqlx::test]
+    async fn admins_backfill_past_summaries(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+
+        let mut backfill = coverage(1, 10);
+        backfill["insert_time"] = json!("2020-01-02T03:04:05Z");
+
+        let req = json_request(Method::POST, "/org/repo/summary", &backfill);
+        assert_eq!(send(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let req = json_request(Method::POST, "/org/repo/summary", &backfill);
+        assert_eq!(send(&app, as_admin(req)).await.status(), StatusCode::OK);
+
+        let history = db::summary::fetch_history(&db, "org", "repo")
+            .await
+            .unwrap();
+        let times: Vec<_> = history.iter().map(|e| e.insert_time.to_rfc3339()).collect();
+        assert_eq!(history.len(), 2);
+        assert_eq!(times[0], "2020-01-02T03:04:05+00:00");
+        assert_eq!(history[0].coverage_summary().unwrap().line.covered, 1);
+        assert_eq!(history[1].coverage_summary().unwrap().line.covered, 5);
+    }
+
+    #[sqlx::test]
+    async fn serves_the_latest_summary_as_flat_json(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        upload(&app, "org", "repo", &coverage(1, 10)).await;
+        upload(&app, "org", "repo", &coverage(1, 4)).await;
+
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let latest = body_json(resp).await;
+        assert_eq!(latest["org"], "org");
+        assert_eq!(latest["repo"], "repo");
+        assert_eq!(latest["line_percent"], 25.0);
+        assert_eq!(latest["branch_percent"], 50.0);
+        assert_eq!(latest["function_percent"], 75.0);
+        assert!(latest.get("extra_percents").is_none());
+
+        // Other repos still have nothing
+        let resp = send(&app, get_request("/org/other/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn admins_see_raw_summaries(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "repo", &coverage(1, 3)).await;
+        upload(&app, "org", "other", &coverage(2, 3)).await;
+
+        let resp = send(&app, get_request("/admin/summaries/raw")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = send(&app, as_admin(get_request("/admin/summaries/raw"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let raw = body_json(resp).await;
+        assert_eq!(raw["truncated"], false);
+        assert!(raw["next_cursor"].is_null());
+
+        // Newest first, with the coverage exactly as stored rather than rounded
+        let summaries = raw["summaries"].as_array().unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0]["repo"], "other");
+        assert_eq!(summaries[1]["repo"], "repo");
+        let line_percent = summaries[1]["coverage"]["line_percent"].as_f64().unwrap();
+        assert!((line_percent - 100.0 / 3.0).abs() < 1e-9, "{line_percent}");
+        assert!(summaries[1]["insert_time"].is_string());
+    }
+
+    #[sqlx::test]
+    async fn caps_the_repos_on_the_root_page(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        for repo in ["a", "b", "c"] {
+            upload(&app, "org", repo, &coverage(1, 2)).await;
+        }
+
+        let Html(page) = root_page(&db, 2).await.unwrap();
+        assert_eq!(page.matches("/trend.html").count(), 2, "{page}");
+        assert!(page.contains("Only the first 2 repos are shown."), "{page}");
+
+        let Html(page) = root_page(&db, 3).await.unwrap();
+        assert_eq!(page.matches("/trend.html").count(), 3, "{page}");
+        assert!(!page.contains("Only the first"), "{page}");
+    }
+
+    #[sqlx::test]
+    async fn lists_a_repos_commits_newest_first(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(&app, get_request("/org/repo/commits")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        for commit in ["aaa", "bbb", "ccc"] {
+            let uri = format!("/org/repo/{commit}/summary");
+            let resp = send(&app, json_request(Method::PUT, &uri, &coverage(1, 2))).await;
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        // Summaries without a commit aren't listed
+        upload(&app, "org", "repo", &coverage(1, 2)).await;
+
+        let commits = body_json(send(&app, get_request("/org/repo/commits")).await).await;
+        let names: Vec<_> = commits
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["commit"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["ccc", "bbb", "aaa"]);
+        assert!(commits[0]["insert_time"].as_i64().unwrap() > 0);
+    }
+
+    #[sqlx::test]
+    async fn builds_an_org_matrix_worst_first(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "a", &coverage(5, 10)).await;
+        upload(&app, "org", "a", &coverage(8, 10)).await;
+        upload(&app, "org", "b", &coverage(1, 10)).await;
+        upload(&app, "org", "c", &coverage(3, 10)).await;
+        upload(&app, "org", "c", &coverage(2, 10)).await;
+        upload(&app, "other", "x", &coverage(0, 10)).await;
+
+        let resp = send(&app, get_request("/api/orgs/org/matrix")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SKIPPED_ROWS_HEADER], "0");
+
+        let matrix = body_json(resp).await;
+        let rows: Vec<_> = matrix
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| {
+                (
+                    row["repo"].as_str().unwrap(),
+                    row["overall_percent"].as_f64().unwrap(),
+                    row["line_percent"].as_f64().unwrap(),
+                    row["line_delta"].as_f64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("b", 31.25, 10.0, None),
+                ("c", 37.5, 20.0, Some(-10.0)),
+                ("a", 75.0, 80.0, Some(30.0)),
+            ]
+        );
+        assert_eq!(matrix[2]["branch_delta"], 0.0);
+        assert_eq!(matrix[2]["function_delta"], 0.0);
+
+        let resp = send(&app, get_request("/api/orgs/nobody/matrix")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn serves_the_latest_summary_of_a_pr(db: PgPool) {
+        let app = test_app(db).await;
+        let with_pr = |line_covered, pr: Option<i32>| {
+            let mut body = coverage(line_covered, 10);
+            body["pr_number"] = json!(pr);
+            body
+        };
+        upload(&app, "org", "repo", &with_pr(1, Some(7))).await;
+        upload(&app, "org", "repo", &with_pr(2, Some(7))).await;
+        upload(&app, "org", "repo", &with_pr(3, Some(8))).await;
+        upload(&app, "org", "repo", &with_pr(4, None)).await;
+
+        let pr = |number: i32| get_request(&format!("/org/repo/pr/{number}/summary"));
+        let summary = body_json(send(&app, pr(7)).await).await;
+        assert_eq!(summary["line_percent"], 20.0);
+        let summary = body_json(send(&app, pr(8)).await).await;
+        assert_eq!(summary["line_percent"], 30.0);
+
+        assert_eq!(send(&app, pr(9)).await.status(), StatusCode::NOT_FOUND);
+
+        // Summaries without a PR are still the repo's latest
+        let summary = body_json(send(&app, get_request("/org/repo/summary")).await).await;
+        assert_eq!(summary["line_percent"], 40.0);
+    }
+
+    #[sqlx::test]
+    async fn skips_corrupt_summaries_in_listings(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        upload(&app, "org", "good", &coverage(1, 2)).await;
+        upload(&app, "org", "bad", &coverage(1, 2)).await;
+        for table in ["summary", "latest_summary"] {
+            sqlx::query(&format!(
+                r#"UPDATE {table} SET coverage = '{{"line_covered": "lots"}}' WHERE repo = 'bad'"#
+            ))
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        let resp = send(&app, get_request("/api/summary/flat")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SKIPPED_ROWS_HEADER], "1");
+        let flat = body_json(resp).await;
+        assert_eq!(flat.as_array().unwrap().len(), 1);
+        assert_eq!(flat[0]["repo"], "good");
+
+        let resp = send(&app, get_request("/api/orgs/org/matrix")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SKIPPED_ROWS_HEADER], "1");
+        assert_eq!(body_json(resp).await[0]["repo"], "good");
+    }
+
+    #[tokio::test]
+    async fn uploads_must_be_json() {
+        let app = lazy_app();
+        let body = coverage(1, 2).to_string();
+
+        let mut req = request(Method::POST, "/org/repo/summary", body.clone());
+        req.headers_mut()
+            .insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(body_string(resp).await.contains("application/json"));
+
+        let req = request(Method::PUT, "/org/repo/abc/summary", body);
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[sqlx::test]
+    async fn adds_and_lists_annotations(db: PgPool) {
+        let app = test_app(db).await;
+        let note = |text: &str| {
+            let body = json!({ "author": "reviewer", "text": text });
+            as_admin(json_request(Method::POST, "/org/repo/annotations", &body))
+        };
+
+        // Notes are attached to the latest summary, so there has to be one
+        assert_eq!(
+            send(&app, note("early")).await.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        upload(&app, "org", "repo", &coverage(1, 2)).await;
+        let latest = body_json(send(&app, get_request("/org/repo/summary")).await).await;
+
+        let resp = send(&app, note("dip expected")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let added = body_json(resp).await;
+        assert_eq!(added["summary_time"], latest["insert_time"]);
+        assert_eq!(added["author"], "reviewer");
+
+        let resp = send(&app, note("refactor in progress")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Only admins and holders of the repo's secret may add notes
+        let body = json!({ "author": "someone", "text": "spam" });
+        let req = json_request(Method::POST, "/org/repo/annotations", &body);
+        assert_eq!(send(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+
+        let listed = body_json(send(&app, get_request("/org/repo/annotations")).await).await;
+        let texts: Vec<_> = listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|a| a["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, ["dip expected", "refactor in progress"]);
+        assert_eq!(listed[0]["id"], added["id"]);
+    }
+
+    #[sqlx::test]
+    async fn read_only_mode_blocks_writes(db: PgPool) {
+        let writable = test_app(db.clone()).await;
+        upload(&writable, "org", "repo", &coverage(1, 2)).await;
+        let config = RouterConfig {
+            read_only: true,
+            ..RouterConfig::default()
+        };
+        let app = configured_app(db, config).await;
+
+        let body = coverage(2, 2);
+        for (method, uri) in [
+            (Method::POST, "/org/repo/summary"),
+            (Method::PUT, "/org/repo/abc/summary"),
+            (Method::POST, "/org/repo/summary/merge"),
+            (Method::PUT, "/org/repo/goals"),
+        ] {
+            let resp = send(&app, json_request(method.clone(), uri, &body)).await;
+            assert_eq!(
+                resp.status(),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "{method} {uri}"
+            );
+        }
+        let req = as_admin(request(Method::DELETE, "/admin/secrets/org/repo", ""));
+        assert_eq!(
+            send(&app, req).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        // Methods no route accepts are still refused as usual
+        let resp = send(&app, get_request("/org/repo/config")).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        // Nothing was stored
+        let latest = body_json(send(&app, get_request("/org/repo/summary")).await).await;
+        assert_eq!(latest["line_percent"], 50.0);
+    }
+
+    #[sqlx::test]
+    async fn read_only_mode_allows_reads(db: PgPool) {
+        let writable = test_app(db.clone()).await;
+        upload(&writable, "org", "repo", &coverage(1, 2)).await;
+        let config = RouterConfig {
+            read_only: true,
+            ..RouterConfig::default()
+        };
+        let app = configured_app(db, config).await;
+
+        for uri in ["/org/repo/summary", "/api/summary", "/org/repo/goals", "/"] {
+            let resp = send(&app, get_request(uri)).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+
+        // These are POSTs, but store nothing
+        let (status, _) = validate(&app, &coverage(2, 2)).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = json!({ "baseline": coverage(1, 2), "current": coverage(2, 2) });
+        let resp = send(&app, json_request(Method::POST, "/org/repo/compare", &body)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn exports_every_summary_as_ndjson(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        // Far more rows than are buffered between the DB and the client at once
+        let count = EXPORT_BUFFER_ROWS * 3 + 1;
+        let entries: Vec<_> = (0..count)
+            .map(|i| {
+                let upload = serde_json::from_value(coverage(i % 10, 10)).unwrap();
+                ("org".to_string(), format!("repo-{i}"), upload)
+            })
+            .collect();
+        db::summary::insert_batch(&db, &entries).await.unwrap();
+
+        let resp = send(&app, get_request("/admin/export.ndjson")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = send(&app, as_admin(get_request("/admin/export.ndjson"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+
+        let body = body_string(resp).await;
+        assert!(body.ends_with('\n'));
+        let rows: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), count);
+        assert_eq!(rows[0]["repo"], "repo-0");
+        assert_eq!(rows[count - 1]["repo"], format!("repo-{}", count - 1));
+        assert_eq!(
+            rows[count - 1]["coverage"]["line_covered"],
+            (count - 1) % 10
+        );
+    }
+
+    #[sqlx::test]
+    async fn combines_a_teams_orgs(db: PgPool) {
+        let app = test_app(db).await;
+        let (team, [alpha, beta]) = TEST_TEAM;
+        upload(&app, alpha, "a", &coverage(1, 10)).await;
+        upload(&app, beta, "b", &coverage(3, 10)).await;
+        upload(&app, "outsider", "c", &coverage(10, 10)).await;
+
+        let resp = send(&app, get_request(&format!("/api/teams/{team}/summary"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SKIPPED_ROWS_HEADER], "0");
+        assert_eq!(
+            body_json(resp).await,
+            json!({
+                "team": team,
+                "orgs": [alpha, beta],
+                "repos": 2,
+                "overall_percent": 37.5,
+                "line_percent": 20.0,
+                "branch_percent": 50.0,
+                "function_percent": 75.0,
+            })
+        );
+
+        let resp = send(&app, get_request("/api/teams/nobody/summary")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn stores_build_urls(db: PgPool) {
+        let app = test_app(db).await;
+        let mut body = coverage(1, 2);
+        body["build_url"] = json!("https://ci.example.com/build/42");
+        upload(&app, "org", "built", &body).await;
+        upload(&app, "org", "unbuilt", &coverage(1, 2)).await;
+
+        body["build_url"] = json!("javascript:alert(1)");
+        let resp = send(&app, json_request(Method::POST, "/org/bad/summary", &body)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let listing = body_json(send(&app, get_request("/api/summary")).await).await;
+        let urls: Vec<_> = listing
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["repo"].as_str().unwrap(), e["build_url"].clone()))
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                ("built", json!("https://ci.example.com/build/42")),
+                ("unbuilt", serde_json::Value::Null),
+            ]
+        );
+    }
+
+    fn host_request(method: Method, uri: &str, host: &str) -> Request<axum::body::Body> {
+        let mut req = request(method, uri, "");
+        req.headers_mut()
+            .insert(header::HOST, host.parse().unwrap());
+        req
+    }
+
+    async fn canonical_app(db: PgPool) -> Router {
+        let config = RouterConfig {
+            canonical_host: Some(Arc::from("coverage.example.com")),
+            ..RouterConfig::default()
+        };
+        configured_app(db, config).await
+    }
+
+    #[sqlx::test]
+    async fn redirects_to_the_canonical_host(db: PgPool) {
+        let app = canonical_app(db).await;
+
+        let req = host_request(Method::GET, "/org/repo/summary?x=1", "old.example.com");
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            "http://coverage.example.com/org/repo/summary?x=1"
+        );
+
+        // Uploads have to keep their method and body
+        let req = host_request(Method::POST, "/org/repo/summary", "old.example.com");
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            "http://coverage.example.com/org/repo/summary"
+        );
+
+        let req = host_request(Method::GET, "/org/repo/summary", "Coverage.Example.com");
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn health_and_metrics_skip_the_canonical_redirect(db: PgPool) {
+        let app = canonical_app(db).await;
+        upload(&app, "org", "repo", &coverage(1, 2)).await;
+
+        for uri in [
+            "/livez",
+            "/readyz",
+            "/org/repo/openmetrics",
+            "/org/repo/pushgateway",
+        ] {
+            let resp = send(&app, host_request(Method::GET, uri, "10.0.0.5:1001")).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unsafe_path_identifiers() {
+        let app = lazy_app();
+
+        for uri in [
+            "/../x/summary",
+            "/a%20b/r/summary",
+            "/org/..%2Fetc/summary",
+            "/org/%3Cscript%3E/trend.html",
+        ] {
+            let resp = send(&app, get_request(uri)).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/a%20b/r/summary", &coverage(1, 2)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(body_string(resp).await.contains("may only contain"));
+    }
+
+    #[sqlx::test]
+    async fn groups_the_latest_summaries_by_branch(db: PgPool) {
+        let app = test_app(db).await;
+        let on_branch = |line_covered, branch: Option<&str>| {
+            let mut body = coverage(line_covered, 10);
+            body["branch"] = json!(branch);
+            body
+        };
+        upload(&app, "org", "repo", &on_branch(1, Some("main"))).await;
+        upload(&app, "org", "repo", &on_branch(2, Some("develop"))).await;
+        upload(&app, "org", "repo", &on_branch(3, Some("main"))).await;
+        // Summaries without a branch aren't grouped under any
+        upload(&app, "org", "repo", &on_branch(4, None)).await;
+
+        let resp = send(&app, get_request("/org/repo/by-branch")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[SKIPPED_ROWS_HEADER], "0");
+
+        let branches = body_json(resp).await;
+        let branches = branches.as_object().unwrap();
+        let percents: Vec<_> = branches
+            .iter()
+            .map(|(branch, flat)| (branch.as_str(), flat["line_percent"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(percents, [("develop", 20.0), ("main", 30.0)]);
+    }
+
+    #[sqlx::test]
+    async fn accepts_form_encoded_uploads(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        let form = |body: &str| {
+            let mut req = request(Method::POST, "/org/repo/summary/form", body.to_string());
+            req.headers_mut().insert(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded".parse().unwrap(),
+            );
+            req
+        };
+
+        let resp = send(
+            &app,
+            form(
+                "line_covered=3&line_total=4&branch_covered=1&branch_total=2\
+                &function_covered=0&function_total=1&branch=main&gcc_version=14.1.0",
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let stored = db::summary::fetch_latest(&db, "org", "repo")
+            .await
+            .unwrap()
+            .unwrap();
+        let coverage = stored.coverage_summary().unwrap();
+        assert_eq!((coverage.line.covered, coverage.line.total), (3, 4));
+        assert_eq!(coverage.line.percent, 75.0);
+        assert_eq!(coverage.function.percent, 0.0);
+        assert_eq!(stored.branch.as_deref(), Some("main"));
+        assert_eq!(stored.tool.gcc_version.as_deref(), Some("14.1.0"));
+
+        // Every count is required
+        let resp = send(&app, form("line_covered=3&line_total=4")).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let resp = send(&app, form("line_covered=5&line_total=4&branch_covered=1&branch_total=2&function_covered=0&function_total=1")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn caches_the_latest_summary_until_the_next_upload(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        upload(&app, "org", "repo", &coverage(1, 10)).await;
+
+        let latest = || async {
+            let resp = send(&app, get_request("/org/repo/summary")).await;
+            body_json(resp).await["line_percent"].clone()
+        };
+        assert_eq!(latest().await, 10.0);
+
+        // Changed behind the server's back, so only a cache miss would see it
+        sqlx::query(
+            r#"UPDATE latest_summary
+                SET coverage = jsonb_set(coverage, '{line_percent}', '99.0')"#,
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(latest().await, 10.0);
+
+        upload(&app, "org", "repo", &coverage(2, 10)).await;
+        assert_eq!(latest().await, 20.0);
+    }
+
+    #[sqlx::test]
+    async fn reports_the_gap_to_each_goal(db: PgPool) {
+        let app = test_app(db).await;
+        let goals = || get_request("/org/repo/goals");
+        let set_goals = |goals: serde_json::Value| {
+            as_admin(json_request(Method::PUT, "/org/repo/goals", &goals))
+        };
+
+        let resp = send(&app, goals()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // 70% of lines, 50% of branches and 75% of functions are covered
+        upload(&app, "org", "repo", &coverage(7, 10)).await;
+
+        let progress = body_json(send(&app, goals()).await).await;
+        assert_eq!(progress["line"]["current"], 70.0);
+        assert!(progress["line"]["goal"].is_null());
+        assert!(progress["line"]["gap"].is_null());
+
+        let resp = send(
+            &app,
+            json_request(
+                Method::PUT,
+                "/org/repo/goals",
+                &json!({ "line_goal": 90.0 }),
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = send(
+            &app,
+            set_goals(json!({ "line_goal": 90.0, "branch_goal": 40.0 })),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let progress = body_json(send(&app, goals()).await).await;
+        assert_eq!(
+            progress["line"],
+            json!({ "current": 70.0, "goal": 90.0, "gap": 20.0 })
+        );
+        // Goals already met leave no gap
+        assert_eq!(
+            progress["branch"],
+            json!({ "current": 50.0, "goal": 40.0, "gap": 0.0 })
+        );
+        assert_eq!(
+            progress["function"],
+            json!({ "current": 75.0, "goal": null, "gap": null })
+        );
+
+        // Setting goals again replaces them all
+        send(&app, set_goals(json!({ "function_goal": 80.0 }))).await;
+        let progress = body_json(send(&app, goals()).await).await;
+        assert!(progress["line"]["goal"].is_null());
+        assert_eq!(progress["function"]["gap"], 5.0);
+    }
+
+    #[sqlx::test]
+    async fn charts_the_trend_of_each_metric(db: PgPool) {
+        let app = test_app(db).await;
+        for (line_covered, time) in [(1, "2024-01-01T00:00:00Z"), (3, "2024-01-02T00:00:00Z")] {
+            let mut body = coverage(line_covered, 4);
+            body["insert_time"] = json!(time);
+            let resp = send(
+                &app,
+                as_admin(json_request(Method::POST, "/org/repo/summary", &body)),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+
+        let resp = send(&app, get_request("/org/repo/trend.html")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page = body_string(resp).await;
+
+        assert!(page.contains("<h1>org/repo</h1>"), "{page}");
+        assert!(
+            page.contains(
+                "Coverage of 2 summaries, from 2024-01-01 00:00 UTC to 2024-01-02 00:00 UTC"
+            ),
+            "{page}"
+        );
+        // The oldest point is on the left and the newest on the right, 200px being 0%
+        assert!(
+            page.contains(
+                r##"<polyline points="0.0,150.0 600.0,50.0" fill="none" stroke="#1f77b4""##
+            ),
+            "{page}"
+        );
+        assert!(
+            page.contains(
+                r##"<polyline points="0.0,100.0 600.0,100.0" fill="none" stroke="#ff7f0e""##
+            ),
+            "{page}"
+        );
+        assert!(page.contains(">line</span> - 75%"), "{page}");
+        assert!(page.contains(">function</span> - 75%"), "{page}");
+        assert!(!page.contains("No coverage has been uploaded"), "{page}");
+    }
+
+    #[sqlx::test]
+    async fn trend_page_shows_when_theres_no_data(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(&app, get_request("/org/repo/trend.html")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let page = body_string(resp).await;
+
+        assert!(
+            page.contains("No coverage has been uploaded for this repo yet."),
+            "{page}"
+        );
+        assert!(!page.contains("<svg"), "{page}");
+    }
+
+    #[sqlx::test]
+    async fn summarizes_the_latest_coverage_as_text(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(&app, get_request("/org/repo/summary.txt")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_string(resp).await, "No coverage yet for org/repo\n");
+
+        upload(
+            &app,
+            "org",
+            "repo",
+            &coverage_of((250, 300), (1, 2), (3, 4)),
+        )
+        .await;
+
+        let resp = send(&app, get_request("/org/repo/summary.txt")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            body_string(resp).await,
+            "line:     83.33% (250/300)\nbranch:   50% (1/2)\nfunction: 75% (3/4)\n"
+        );
+    }
+
+    #[sqlx::test]
+    async fn records_rejected_uploads(db: PgPool) {
+        let app = test_app(db).await;
+        let failures = || as_admin(get_request("/admin/failures"));
+
+        let resp = send(&app, failures()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_json(resp).await, json!([]));
+
+        let body = json!({ "line_covered": 1 });
+        let resp = send(&app, json_request(Method::POST, "/org/repo/summary", &body)).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        // The client still gets the extractor's explanation
+        assert!(body_string(resp).await.contains("missing field"));
+
+        let mut req = request(Method::POST, "/org/other/summary/form", "line_covered=1");
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded".parse().unwrap(),
+        );
+        let resp = send(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let resp = send(&app, get_request("/admin/failures")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let recorded = body_json(send(&app, failures()).await).await;
+        let recorded = recorded.as_array().unwrap();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0]["repo"], "other");
+        assert_eq!(recorded[0]["payload"], "line_covered=1");
+        assert_eq!(recorded[1]["org"], "org");
+        assert_eq!(recorded[1]["repo"], "repo");
+        assert_eq!(recorded[1]["payload"], body.to_string());
+        let error = recorded[1]["error"].as_str().unwrap();
+        assert!(error.starts_with("Rejected upload: "), "{error}");
+        assert!(error.contains("missing field"), "{error}");
+        assert!(recorded[1]["failed_at"].as_i64().unwrap() > 0);
+    }
+
+    #[sqlx::test]
+    async fn diffs_against_a_named_baseline(db: PgPool) {
+        let app = test_app(db).await;
+        let set_baseline = |name: &str| {
+            let uri = format!("/org/repo/baseline?name={name}");
+            as_admin(request(Method::POST, &uri, axum::body::Body::empty()))
+        };
+        let delta = |name: &str| get_request(&format!("/org/repo/delta?baseline={name}"));
+
+        // Baselines point at a summary, so there has to be one
+        let resp = send(&app, set_baseline("release-1.0")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        upload(&app, "org", "repo", &coverage(5, 10)).await;
+
+        let resp = send(
+            &app,
+            request(
+                Method::POST,
+                "/org/repo/baseline?name=release-1.0",
+                axum::body::Body::empty(),
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = send(&app, set_baseline("")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = send(&app, set_baseline("release-1.0")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let marked = body_json(resp).await;
+        assert_eq!(marked["name"], "release-1.0");
+
+        upload(&app, "org", "repo", &coverage(8, 10)).await;
+
+        let resp = send(&app, delta("release-1.0")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let diff = body_json(resp).await;
+        assert_eq!(diff["baseline_time"], marked["insert_time"]);
+        assert_eq!(
+            diff["line"],
+            json!({ "current": 80.0, "baseline": 50.0, "delta": 30.0, "change": "improved" })
+        );
+        assert_eq!(diff["branch"]["delta"], 0.0);
+        assert_eq!(diff["branch"]["change"], "held");
+
+        let resp = send(&app, delta("release-2.0")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Marking the name again moves it to the latest summary
+        send(&app, set_baseline("release-1.0")).await;
+        let diff = body_json(send(&app, delta("release-1.0")).await).await;
+        assert_eq!(diff["line"]["delta"], 0.0);
+    }
+
+    #[sqlx::test]
+    async fn enforces_org_quotas(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        let set_quota = |org: &str, quota: serde_json::Value| {
+            as_admin(json_request(
+                Method::PUT,
+                &format!("/admin/quotas/{org}"),
+                &quota,
+            ))
+        };
+        let try_upload = |org: &str| {
+            let uri = format!("/{org}/repo/summary");
+            send(&app, json_request(Method::POST, &uri, &coverage(1, 2)))
+        };
+        let stored = |org: &'static str| {
+            let db = db.clone();
+            async move {
+                let (count,): (i64,) =
+                    sqlx::query_as("SELECT count(*) FROM summary WHERE org = $1")
+                        .bind(org)
+                        .fetch_one(&db)
+                        .await
+                        .unwrap();
+                count
+            }
+        };
+
+        // Orgs without a quota are unlimited
+        let resp = send(&app, as_admin(get_request("/admin/quotas/capped"))).await;
+        assert_eq!(body_json(resp).await["max_summaries"], json!(null));
+
+        let resp = send(&app, set_quota("capped", json!({ "max_summaries": 2 }))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(
+            &app,
+            set_quota("slow", json!({ "max_uploads_per_hour": 1 })),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let pruned = json!({ "max_summaries": 2, "prune_oldest": true });
+        send(&app, set_quota("pruned", pruned)).await;
+
+        // Staying under the quota is fine, going over isn't and stores nothing
+        for _ in 0..2 {
+            assert_eq!(try_upload("capped").await.status(), StatusCode::OK);
+        }
+        let resp = try_upload("capped").await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(body_string(resp)
+            .await
+            .contains("quota of 2 stored summaries"));
+        assert_eq!(stored("capped").await, 2);
+
+        assert_eq!(try_upload("slow").await.status(), StatusCode::OK);
+        assert_eq!(
+            try_upload("slow").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        for _ in 0..3 {
+            assert_eq!(try_upload("pruned").await.status(), StatusCode::OK);
+        }
+        assert_eq!(stored("pruned").await, 2);
+
+        for _ in 0..3 {
+            assert_eq!(try_upload("unlimited").await.status(), StatusCode::OK);
+        }
+        assert_eq!(stored("unlimited").await, 3);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_uploads_cant_overrun_a_quota(db: PgPool) {
+        let app = test_app(db.clone()).await;
+        let quota = json!({ "max_summaries": 3 });
+        send(
+            &app,
+            as_admin(json_request(Method::PUT, "/admin/quotas/org", &quota)),
+        )
+        .await;
+
+        // Each checks the count within its insert's transaction, so only three can win
+        let uploads = (0..8).map(|i| {
+            let uri = format!("/org/repo-{i}/summary");
+            send(&app, json_request(Method::POST, &uri, &coverage(1, 2)))
+        });
+        let statuses: Vec<StatusCode> = futures_util::future::join_all(uploads)
+            .await
+            .iter()
+            .map(|resp| resp.status())
+            .collect();
+
+        let stored = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+        let refused = statuses
+            .iter()
+            .filter(|s| **s == StatusCode::FORBIDDEN)
+            .count();
+        assert_eq!((stored, refused), (3, 5), "{statuses:?}");
+
+        let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM summary")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[tokio::test]
+    async fn render_errors_get_a_generic_page() {
+        let missing_template = render_page("missing.html", &base_context());
+        // trend.html needs the org and repo
+        let missing_variable = render_page("trend.html", &base_context());
+
+        for result in [missing_template, missing_variable] {
+            let err = result.unwrap_err();
+            assert!(matches!(err, AppError::Render(_)), "{err:?}");
+
+            let resp = err.into_response();
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(
+                resp.headers()[header::CONTENT_TYPE],
+                "text/html; charset=utf-8"
+            );
+            // Nothing about the template leaks to the client
+            assert_eq!(body_string(resp).await, RENDER_ERROR_PAGE);
+        }
+    }
+
+    #[sqlx::test]
+    async fn lists_the_metrics_being_measured(db: PgPool) {
+        let app = test_app(db).await;
+
+        let resp = send(&app, get_request("/org/repo/metrics")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let line_only = json!({
+            "branch_covered": 0, "branch_total": 0, "branch_percent": 0.0,
+            "function_covered": 0, "function_total": 0, "function_percent": 0.0,
+            "line_covered": 3, "line_total": 4, "line_percent": 75.0,
+        });
+        upload(&app, "org", "repo", &line_only).await;
+
+        let resp = send(&app, get_request("/org/repo/metrics")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(resp).await,
+            json!({ "line": true, "branch": false, "function": false })
+        );
+
+        upload(&app, "org", "repo", &coverage(1, 2)).await;
+        let resp = send(&app, get_request("/org/repo/metrics")).await;
+        assert_eq!(
+            body_json(resp).await,
+            json!({ "line": true, "branch": true, "function": true })
+        );
+    }
+
+    #[sqlx::test]
+    async fn puts_summaries_per_commit(db: PgPool) {
+        let app = test_app(db).await;
+        let put = |commit: &str, body: &serde_json::Value| {
+            let uri = format!("/org/repo/{commit}/summary");
+            send(&app, json_request(Method::PUT, &uri, body))
+        };
+
+        let resp = put("abc", &coverage(1, 10)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let ack = body_json(resp).await;
+        assert_eq!(ack["replaced"], false);
+
+        // Another commit adds a summary rather than replacing one
+        let resp = put("def", &coverage(2, 10)).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = put("def", &coverage(3, 10)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // The body may name the commit too, but only the path's
+        let mut body = coverage(4, 10);
+        body["commit"] = json!("other");
+        let resp = put("abc", &body).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        body["commit"] = json!("abc");
+        assert_eq!(put("abc", &body).await.status(), StatusCode::OK);
+
+        // Only admins may backfill
+        let mut body = coverage(5, 10);
+        body["insert_time"] = json!("2020-01-01T00:00:00Z");
+        assert_eq!(put("ghi", &body).await.status(), StatusCode::UNAUTHORIZED);
+
+        let export = body_json(send(&app, get_request("/org/repo/history.json")).await).await;
+        let mut covered: Vec<_> = export["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["commit"].clone(), e["coverage"]["line_covered"].clone()))
+            .collect();
+        covered.sort_by_key(|(commit, _)| commit.to_string());
+        assert_eq!(
+            covered,
+            [(json!("abc"), json!(4)), (json!("def"), json!(3))]
+        );
+    }
+
+    #[tokio::test]
+    async fn compares_two_posted_summaries() {
+        // Nothing is read or stored, so no database is needed
+        let app = lazy_app();
+        let compare = |baseline: serde_json::Value, current: serde_json::Value| {
+            let body = json!({ "baseline": baseline, "current": current });
+            send(&app, json_request(Method::POST, "/org/repo/compare", &body))
+        };
+
+        let resp = compare(
+            coverage_of((1, 4), (1, 2), (3, 4)),
+            coverage_of((3, 4), (1, 2), (1, 4)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let diff = body_json(resp).await;
+        assert_eq!(
+            diff["line"],
+            json!({ "current": 75.0, "baseline": 25.0, "delta": 50.0, "change": "improved" })
+        );
+        assert_eq!(diff["branch"]["delta"], 0.0);
+        assert_eq!(diff["branch"]["change"], "held");
+        assert_eq!(diff["function"]["delta"], -50.0);
+        assert_eq!(diff["function"]["change"], "regressed");
+
+        // Both summaries are validated, with errors naming which was wrong
+        let resp = compare(coverage(5, 4), coverage(1, 2)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let errors = body_json(resp).await["errors"].clone();
+        let errors = errors.as_array().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].as_str().unwrap().starts_with("baseline: line"),
+            "{errors:?}"
+        );
+    }
+
+    #[sqlx::test]
+    async fn renders_repo_descriptions_on_the_root_page(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "described", &coverage(1, 2)).await;
+        upload(&app, "org", "plain", &coverage(1, 2)).await;
+
+        let info = json!({ "description": "Parses **gcov** output <script>alert(1)</script>" });
+        let resp = send(
+            &app,
+            json_request(Method::PUT, "/org/described/info", &info),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = send(
+            &app,
+            as_admin(json_request(Method::PUT, "/org/described/info", &info)),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let page = body_string(send(&app, get_request("/")).await).await;
+        let line = |repo: &str| {
+            page.lines()
+                .find(|line| line.contains(&format!("<li>{repo} - ")))
+                .unwrap_or_else(|| panic!("{repo} isn't listed: {page}"))
+                .to_string()
+        };
+
+        let described = line("described");
+        assert!(
+            described.contains("Parses <strong>gcov</strong> output"),
+            "{described}"
+        );
+        assert!(described.contains("&lt;script&gt;"), "{described}");
+        assert!(!described.contains("<script>"), "{described}");
+        assert!(!line("plain").contains("<p>"), "{page}");
+    }
+
+    #[sqlx::test]
+    async fn filters_the_summary_list_by_upload_count(db: PgPool) {
+        let app = test_app(db).await;
+        let mut nightly = coverage(1, 2);
+        nightly["labels"] = json!(["nightly"]);
+        for (repo, uploads) in [("a", 3), ("b", 2), ("c", 1)] {
+            for _ in 1..uploads {
+                upload(&app, "org", repo, &coverage(1, 2)).await;
+            }
+            upload(&app, "org", repo, &nightly).await;
+        }
+
+        let repos = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let resp = send(&app, get_request(uri)).await;
+                assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+                let summaries = body_json(resp).await;
+                let repos: Vec<String> = summaries
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|s| s["repo"].as_str().unwrap().to_string())
+                    .collect();
+                repos
+            }
+        };
+
+        assert_eq!(repos("/api/summary").await, ["a", "b", "c"]);
+        assert_eq!(repos("/api/summary?min_uploads=2").await, ["a", "b"]);
+        assert_eq!(repos("/api/summary?min_uploads=3").await, ["a"]);
+        assert!(repos("/api/summary?min_uploads=4").await.is_empty());
+        // Combines with the label filter, still counting every upload
+        assert_eq!(
+            repos("/api/summary?label=nightly&min_uploads=2").await,
+            ["a", "b"]
+        );
+    }
+
+    // Assumes `REGRESSION_TOLERANCE` isn't set, leaving the default of 0.1
+    #[sqlx::test]
+    async fn tiny_drops_hold_coverage(db: PgPool) {
+        let app = test_app(db).await;
+        upload(&app, "org", "repo", &coverage(1000, 2000)).await;
+
+        // 50% to 49.95%, within the tolerance
+        let resp = send(
+            &app,
+            json_request(Method::POST, "/org/repo/summary", &coverage(999, 2000)),
+        )
+        .await;
+        let ack = body_json(resp).await;
+        assert_eq!(ack["line_delta"], -0.05);
+        assert_eq!(ack["change"], "held");
+    }
+
+    // Assumes `REGRESSION_TOLERANCE` isn't set, leaving the default of 0.1
+    #[sqlx::test]
+    async fn classifies_velocity_by_the_change_over_the_window(db: PgPool) {
+        let app = test_app(db).await;
+        let backfill = |repo: &str, days_ago: i64, covered: usize| {
+            let mut body = coverage(covered, 100_000);
+            let time = chrono::Utc::now() - chrono::TimeDelta::days(days_ago);
+            body["insert_time"] = json!(time.to_rfc3339());
+            let uri = format!("/org/{repo}/summary");
+            send(&app, as_admin(json_request(Method::POST, &uri, &body)))
+        };
+        let velocity = |uri: &'static str| {
+            let app = app.clone();
+            async move { body_json(send(&app, get_request(uri)).await).await }
+        };
+
+        // Losing 0.01pp a day is well within the tolerance day to day, but not over 30 days
+        backfill("slipping", 20, 50_000).await;
+        backfill("slipping", 10, 49_900).await;
+        let resp = velocity("/org/slipping/velocity").await;
+        assert_eq!(resp["slope_per_day"], -0.01);
+        assert_eq!(resp["direction"], "regressed");
+        let resp = velocity("/org/slipping/velocity?window=5d").await;
+        assert!(resp["slope_per_day"].is_null());
+
+        backfill("steady", 20, 50_000).await;
+        backfill("steady", 10, 49_997).await;
+        let resp = velocity("/org/steady/velocity").await;
+        assert_eq!(resp["direction"], "held");
+    }
+
+    #[sqlx::test]
+    async fn charts_the_trend_as_a_png(db: PgPool) {
+        let app = test_app(db).await;
+        let chart = || async {
+            let resp = send(&app, get_request("/org/repo/trend.png")).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[header::CONTENT_TYPE], "image/png");
+
+            let png = body_bytes(resp).await;
+            let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut pixels).unwrap();
+            assert_eq!((info.width, info.height), (620, 220));
+            move |x: usize, y: usize| -> [u8; 3] {
+                let offset = (y * 620 + x) * 3;
+                pixels[offset..offset + 3].try_into().unwrap()
+            }
+        };
+
+        // Without history only the gridlines are drawn, every 25% from the bottom up
+        let pixel = chart().await;
+        assert_eq!(pixel(100, 210), [221, 221, 221]);
+        assert_eq!(pixel(100, 60), [221, 221, 221]);
+        assert_eq!(pixel(100, 100), [255, 255, 255]);
+
+        // A single summary is drawn as a dot per metric, in the middle of the chart
+        upload(&app, "org", "repo", &coverage(1, 4)).await;
+        let pixel = chart().await;
+        let [(_, line, _), (_, branch, _), (_, function, _)] = TREND_METRICS;
+        assert_eq!(pixel(310, 160), line);
+        assert_eq!(pixel(310, 110), branch);
+        assert_eq!(pixel(310, 60), function);
+        // 25% is on a gridline, which the dot only covers in the middle
+        assert_eq!(pixel(100, 160), [221, 221, 221]);
+    }
+
+    #[sqlx::test]
+    async fn abandons_requests_past_the_deadline(
+        pool_options: sqlx::postgres::PgPoolOptions,
+        connect_options: sqlx::postgres::PgConnectOptions,
+    ) {
+        let timeout = Duration::from_millis(200);
+        // Connections are set up as `db::connect_and_setup` would with this deadline
+        let statement_timeout = db::capped_statement_timeout(0, Some(timeout));
+        let connect_options =
+            connect_options.options([("statement_timeout", statement_timeout.to_string())]);
+        let db = pool_options.connect_with(connect_options).await.unwrap();
+        let config = RouterConfig {
+            request_timeout: Some(timeout),
+            ..RouterConfig::default()
+        };
+        let app = configured_app(db.clone(), config).await;
+        upload(&app, "org", "repo", &coverage(1, 2)).await;
+
+        // Postgres cancels any query running past the deadline
+        let err = sqlx::query("SELECT pg_sleep(5)")
+            .execute(&db)
+            .await
+            .unwrap_err();
+        let code = err.as_database_error().and_then(|e| e.code());
+        assert_eq!(code.as_deref(), Some("57014"), "{err}");
+
+        // Hold a lock the latest summary's query has to wait for
+        let mut blocker = db.begin().await.unwrap();
+        sqlx::query("LOCK TABLE latest_summary IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut *blocker)
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+
+        // The abandoned query doesn't linger on the server either
+        let waiting = || async {
+            let (count,): (i64,) = sqlx::query_as(
+                r#"SELECT count(*) FROM pg_stat_activity
+                    WHERE state = 'active' AND query LIKE '%FROM latest_summary%'
+                        AND pid <> pg_backend_pid()"#,
+            )
+            .fetch_one(&db)
+            .await
+            .unwrap();
+            count
+        };
+        for _ in 0..50 {
+            if waiting().await == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(waiting().await, 0);
+
+        blocker.rollback().await.unwrap();
+        let resp = send(&app, get_request("/org/repo/summary")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn extra_metrics_survive_a_round_trip(db: PgPool) {
+        let app = test_app(db).await;
+
+        let mut body = coverage(5, 10);
+        body["extra"] = json!({ "condition": { "covered": 2, "total": 3, "percent": 66.666666 } });
+        upload(&app, "org", "repo", &body).await;
+
+        let latest = body_json(send(&app, get_request("/org/repo/summary")).await).await;
+        assert_eq!(latest["line_percent"], 50.0);
+        assert_eq!(latest["extra_percents"], json!({ "condition": 66.67 }));
+
+        let listing = body_json(send(&app, get_request("/api/summary")).await).await;
+        assert_eq!(
+            listing[0]["coverage"]["extra"]["condition"],
+            json!({ "covered": 2, "total": 3, "percent": 66.67 })
+        );
+    }
+
+    #[sqlx::test]
+    async fn overviews_orgs_most_recently_active_first(db: PgPool) {
+        let app = test_app(db).await;
+
+        for (org, repo, time) in [
+            ("quiet", "a", "2020-01-02T03:04:05Z"),
+            ("quiet", "b", "2021-03-04T05:06:07Z"),
+            // Fractions of a second are dropped rather than rounded up
+            ("busy", "c", "2022-05-06T07:08:09.9Z"),
+        ] {
+            let mut backfill = coverage(5, 10);
+            backfill["insert_time"] = json!(time);
+            let req = json_request(Method::POST, &format!("/{org}/{repo}/summary"), &backfill);
+            assert_eq!(send(&app, as_admin(req)).await.status(), StatusCode::OK);
+        }
+
+        let resp = send(&app, get_request("/api/orgs")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(resp).await,
+            json!([
+                { "org": "busy", "repos": 1, "last_updated": 1651820889 },
+                { "org": "quiet", "repos": 2, "last_updated": 1614834367 },
+            ])
+        );
+    }
+
+    /// A status posted to `FakeGitea`, with the path it was posted to and its `Authorization` header
+    #[derive(Debug)]
+    struct PostedStatus {
+        path: String,
+        authorization: String,
+        body: serde_json::Value,
+    }
+
+    /// A Gitea instance on a local port, passing every status posted to it down a channel and
+    /// replying with a fixed status code
+    struct FakeGitea {
+        url: String,
+        posted: tokio::sync::mpsc::UnboundedReceiver<PostedStatus>,
+    }
+
+    impl FakeGitea {
+        async fn start(reply: StatusCode) -> FakeGitea {
+            let (sender, posted) = tokio::sync::mpsc::unbounded_channel();
+            let statuses = Router::new().route(
+                "/api/v1/repos/:org/:repo/statuses/:commit",
+                post(
+                    move |uri: axum::http::Uri,
+                          headers: HeaderMap,
+                          Json(body): Json<serde_json::Value>| async move {
+                        let authorization = headers[header::AUTHORIZATION].to_str().unwrap();
+                        let _ = sender.send(PostedStatus {
+                            path: uri.path().to_string(),
+                            authorization: authorization.to_string(),
+                            body,
+                        });
+                        (reply, "gitea says no")
+                    },
+                ),
+            );
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, statuses).await.unwrap() });
+
+            FakeGitea { url, posted }
+        }
+
+        /// An app over `db` posting commit statuses to this instance
+        async fn app(&self, db: PgPool) -> Router {
+            let client = GiteaClient::new(&self.url, "gitea-token".to_string(), true, 4).unwrap();
+            let config = RouterConfig {
+                gitea: Some(Arc::new(client)),
+                ..Default::default()
+            };
+
+            configured_app(db, config).await
+        }
+
+        async fn next_status(&mut self) -> PostedStatus {
+            tokio::time::timeout(Duration::from_secs(5), self.posted.recv())
+                .await
+                .expect("no status was posted")
+                .unwrap()
+        }
+    }
+
+    #[sqlx::test]
+    async fn posts_commit_statuses_to_gitea(db: PgPool) {
+        let mut gitea = FakeGitea::start(StatusCode::CREATED).await;
+        let app = gitea.app(db).await;
+
+        // Only uploads of a commit have something to post a status to
+        upload(&app, "org", "repo", &coverage(1, 10)).await;
+
+        let mut body = coverage(5, 10);
+        body["commit"] = json!("abc123");
+        upload(&app, "org", "repo", &body).await;
+
+        let status = gitea.next_status().await;
+        assert_eq!(status.path, "/api/v1/repos/org/repo/statuses/abc123");
+        assert_eq!(status.authorization, "token gitea-token");
+        assert_eq!(
+            status.body,
+            json!({ "state": "success", "context": "coverage", "description": "50% line coverage" })
+        );
+        assert!(gitea.posted.try_recv().is_err());
+    }
+
+    #[sqlx::test]
+    async fn logs_statuses_gitea_refuses(db: PgPool) {
+        let (logs, _guard) = capture_logs();
+        let mut gitea = FakeGitea::start(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let app = gitea.app(db).await;
+
+        let mut body = coverage(5, 10);
+        body["commit"] = json!("abc123");
+        let resp = send(&app, json_request(Method::POST, "/org/repo/summary", &body)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        gitea.next_status().await;
+
+        // The failure is only logged once the reply has been read
+        let expected = "Failed to post commit status for org/repo@abc123: Gitea replied with 500 \
+            Internal Server Error: gitea says no";
+        for _ in 0..50 {
+            if logs.contents().contains(expected) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(logs.contents().contains(expected), "{}", logs.contents());
+        assert!(logs.contents().contains("WARN"));
+    }
 }
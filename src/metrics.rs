@@ -0,0 +1,126 @@
+use crate::db::summary::{Coverage, CoverageSummary};
+use std::fmt::Write;
+
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+pub const PROMETHEUS_TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Escapes a label value as required by the OpenMetrics/Prometheus text formats
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Formats a coverage summary as OpenMetrics gauges, e.g. `coverage_line_percent{org="..",repo=".."} 83.3`
+pub fn format_openmetrics(org: &str, repo: &str, summary: &CoverageSummary) -> String {
+    let labels = format!(
+        r#"org="{}",repo="{}""#,
+        escape_label_value(org),
+        escape_label_value(repo)
+    );
+
+    let mut out = format_gauges(&labels, summary);
+    out.push_str("# EOF\n");
+
+    out
+}
+
+/// Formats a coverage summary in the Prometheus text format for pushing to a Pushgateway, labelled
+/// with the org as the job and the repo as the instance. It's meant to be pushed to
+/// `/metrics/job/{org}/instance/{repo}` so the labels agree with the grouping key.
+pub fn format_pushgateway(org: &str, repo: &str, summary: &CoverageSummary) -> String {
+    let labels = format!(
+        r#"job="{}",instance="{}""#,
+        escape_label_value(org),
+        escape_label_value(repo)
+    );
+
+    format_gauges(&labels, summary)
+}
+
+/// Formats a gauge for each count and percentage of a summary, all with the same `labels`
+fn format_gauges(labels: &str, summary: &CoverageSummary) -> String {
+    let metrics: [(&str, &str, &Coverage); 3] = [
+        ("line", "lines", &summary.line),
+        ("branch", "branches", &summary.branch),
+        ("function", "functions", &summary.function),
+    ];
+
+    let mut out = String::new();
+    for (name, plural, coverage) in metrics {
+        let values = [
+            (
+                "percent",
+                format!("Percentage of {plural} covered"),
+                coverage.percent,
+            ),
+            (
+                "covered",
+                format!("Number of {plural} covered"),
+                coverage.covered as f64,
+            ),
+            (
+                "total",
+                format!("Total number of {plural}"),
+                coverage.total as f64,
+            ),
+        ];
+
+        for (suffix, help, value) in values {
+            let metric = format!("coverage_{name}_{suffix}");
+            // Writing to a `String` can't fail
+            let _ = writeln!(out, "# TYPE {metric} gauge");
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "{metric}{{{labels}}} {value}");
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> CoverageSummary {
+        CoverageSummary {
+            branch: Coverage::from_counts(1, 4),
+            function: Coverage::from_counts(3, 4),
+            line: Coverage::from_counts(5, 10),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label_value("plain"), "plain");
+        assert_eq!(escape_label_value("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+
+    #[test]
+    fn formats_openmetrics() {
+        let out = format_openmetrics("org", "re\"po", &summary());
+
+        assert!(out.ends_with("\n# EOF\n"), "{out}");
+        assert_eq!(out.matches("# EOF").count(), 1);
+        assert!(
+            out.contains("# TYPE coverage_line_percent gauge\n"),
+            "{out}"
+        );
+        assert!(out.contains("# HELP coverage_branch_total Total number of branches\n"));
+        assert!(out.contains("coverage_line_percent{org=\"org\",repo=\"re\\\"po\"} 50\n"));
+        assert!(out.contains("coverage_function_covered{org=\"org\",repo=\"re\\\"po\"} 3\n"));
+        assert_eq!(out.lines().filter(|l| !l.starts_with('#')).count(), 9);
+    }
+    #[test]
+    fn formats_pushgateway() {
+        let out = format_pushgateway("org", "repo", &summary());
+
+        assert!(!out.contains("# EOF"), "{out}");
+        assert!(out.contains("coverage_line_total{job=\"org\",instance=\"repo\"} 10\n"));
+        assert!(out.contains("coverage_branch_percent{job=\"org\",instance=\"repo\"} 25\n"));
+        assert!(!out.contains("org=\""), "{out}");
+    }
+}
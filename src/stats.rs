@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+/// Summary statistics of a series of values, e.g. a repo's line coverage over its history
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// Population standard deviation, i.e. how much the values wander from the mean
+    pub std_dev: f64,
+}
+
+impl Stats {
+    /// Computes the statistics of `values`, ignoring NaNs, or returns `None` if that leaves nothing
+    pub fn of(values: &[f64]) -> Option<Stats> {
+        let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let (&min, &max) = (sorted.first()?, sorted.last()?);
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+
+        // The two middle values, which are the same one when there's an odd number
+        let median = (sorted[(sorted.len() - 1) / 2] + sorted[sorted.len() / 2]) / 2.0;
+
+        let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+        Some(Stats {
+            min,
+            max,
+            mean,
+            median,
+            std_dev: variance.sqrt(),
+        })
+    }
+
+    /// Rounds every statistic with `f`, e.g. to the configured number of decimal places
+    pub fn rounded(self, f: impl Fn(f64) -> f64) -> Stats {
+        Stats {
+            min: f(self.min),
+            max: f(self.max),
+            mean: f(self.mean),
+            median: f(self.median),
+            std_dev: f(self.std_dev),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_values() {
+        let stats = Stats::of(&[4.0, 2.0, 8.0, 6.0]).unwrap();
+        assert_eq!(stats.min, 2.0);
+        assert_eq!(stats.max, 8.0);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 5.0);
+        assert_eq!(stats.std_dev, 5.0f64.sqrt());
+    }
+
+    #[test]
+    fn median_of_an_odd_count() {
+        assert_eq!(Stats::of(&[9.0, 1.0, 5.0]).unwrap().median, 5.0);
+    }
+
+    #[test]
+    fn single_value() {
+        let stats = Stats::of(&[42.0]).unwrap();
+        assert_eq!((stats.min, stats.max, stats.median), (42.0, 42.0, 42.0));
+        assert_eq!(stats.std_dev, 0.0);
+    }
+
+    #[test]
+    fn ignores_nans() {
+        assert_eq!(Stats::of(&[]), None);
+        assert_eq!(Stats::of(&[f64::NAN]), None);
+        assert_eq!(Stats::of(&[f64::NAN, 3.0]), Stats::of(&[3.0]));
+    }
+
+    #[test]
+    fn rounds_each_statistic() {
+        let stats = Stats::of(&[1.25, 2.5]).unwrap().rounded(f64::floor);
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 2.0, 1.0));
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::fmt;
+use std::str::FromStr;
+
+const DEFAULT_WINDOW_DAYS: u32 = 30;
+const SECS_PER_DAY: f64 = 86400.0;
+
+/// How far back to look when computing velocity, written as a number of days like `30d`
+#[derive(DeserializeFromStr, SerializeDisplay, Clone, Copy, Debug)]
+pub struct Window {
+    pub days: u32,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            days: DEFAULT_WINDOW_DAYS,
+        }
+    }
+}
+
+impl Window {
+    /// When a window ending at `now` starts, or `None` if that's before the earliest representable
+    /// time
+    pub fn start(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        TimeDelta::try_days(self.days.into()).and_then(|window| now.checked_sub_signed(window))
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('d').and_then(|days| days.parse().ok()) {
+            Some(days) if days > 0 => Ok(Window { days }),
+            _ => Err(format!(
+                "invalid window {s:?}, expected a number of days like 30d"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d", self.days)
+    }
+}
+
+/// Fits a least-squares line through `(unix timestamp, percent)` points, returning its slope in
+/// percentage points per day. There's no slope without at least two distinct timestamps.
+pub fn slope_per_day(points: &[(i64, f64)]) -> Option<f64> {
+    let first = points.first()?.0;
+    // Measure from the first point so the sums stay small enough to be precise
+    let days: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(t, percent)| ((t - first) as f64 / SECS_PER_DAY, percent))
+        .collect();
+
+    let n = days.len() as f64;
+    let mean_x = days.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = days.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let covariance: f64 = days.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = days.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    Some(covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!("30d".parse::<Window>().unwrap().days, 30);
+        assert_eq!(Window::default().to_string(), "30d");
+
+        for invalid in ["", "d", "0d", "30", "-1d", "1.5d", "30days", "99999999999d"] {
+            assert!(invalid.parse::<Window>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn window_start() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let start = Window { days: 2 }.start(now).unwrap();
+        assert_eq!((now - start).num_days(), 2);
+
+        // Far enough back to underflow chrono's range, which used to panic
+        assert!(Window { days: u32::MAX }.start(now).is_none());
+        assert!(Window { days: 100_000_000 }.start(now).is_none());
+    }
+
+    #[test]
+    fn slope_of_a_line() {
+        let day = SECS_PER_DAY as i64;
+        let points = [(1000, 50.0), (1000 + day, 51.0), (1000 + 3 * day, 53.0)];
+        let slope = slope_per_day(&points).unwrap();
+        assert!((slope - 1.0).abs() < 1e-9, "{slope}");
+
+        let falling = [(0, 80.0), (2 * day, 70.0)];
+        assert!((slope_per_day(&falling).unwrap() + 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_slope_without_two_timestamps() {
+        assert_eq!(slope_per_day(&[]), None);
+        assert_eq!(slope_per_day(&[(100, 50.0)]), None);
+        assert_eq!(slope_per_day(&[(100, 50.0), (100, 60.0)]), None);
+    }
+}